@@ -0,0 +1,101 @@
+//! Measures how fast text is translated with dictionaries of different sizes,
+//! and how long compiling those dictionaries takes. Run it with `cargo bench`.
+
+use criterion::{BatchSize, BenchmarkId, Criterion, Throughput, criterion_group, criterion_main};
+use std::hint::black_box;
+use tranclator::{CapitalizationMode, Language, Translation, Translator};
+
+const DICT_SIZES: [usize; 3] = [10, 1_000, 10_000];
+const INPUT_SIZES: [usize; 2] = [1_000, 100_000];
+
+/// Returns a language with `size` made-up words, each translated to the same
+/// word in upper case.
+fn language(size: usize) -> Language {
+    Language {
+        name: format!("bench-{size}"),
+        lower_mode: CapitalizationMode::Preserve,
+        dict: (0..size)
+            .map(|i| (word(i), Translation::Single(word(i).to_uppercase())))
+            .collect(),
+        ..Default::default()
+    }
+}
+
+/// Returns a made-up word, a different one for every `i`.
+fn word(i: usize) -> String {
+    let letters = b"abcdefghijklmnopqrstuvwxyz";
+    let mut word = String::new();
+    let mut i = i;
+
+    loop {
+        word.push(letters[i % letters.len()] as char);
+        i /= letters.len();
+        if i == 0 {
+            break word;
+        }
+    }
+}
+
+/// Returns about `size` bytes of text, with roughly every other word in the
+/// dictionary of a language with `dict_size` words.
+fn input(size: usize, dict_size: usize) -> String {
+    let mut text = String::with_capacity(size + 16);
+    let mut i = 0;
+
+    while text.len() < size {
+        if i % 2 == 0 {
+            text.push_str(&word(i * 7 % dict_size));
+        } else {
+            text.push_str("filler");
+        }
+        text.push(if i % 12 == 11 { '\n' } else { ' ' });
+        i += 1;
+    }
+
+    text
+}
+
+fn bench_translate(c: &mut Criterion) {
+    for dict_size in DICT_SIZES {
+        let mut translator = Translator::builder()
+            .language(language(dict_size))
+            .seed(0)
+            .build()
+            .expect("the language is set");
+        let mut group = c.benchmark_group(format!("translate/{dict_size}-entries"));
+
+        for input_size in INPUT_SIZES {
+            let text = input(input_size, dict_size);
+            group.throughput(Throughput::Bytes(text.len() as u64));
+            group.bench_with_input(BenchmarkId::from_parameter(input_size), &text, |b, text| {
+                b.iter(|| translator.translate(black_box(text)));
+            });
+        }
+
+        group.finish();
+    }
+}
+
+fn bench_compile(c: &mut Criterion) {
+    let mut group = c.benchmark_group("compile");
+
+    for dict_size in DICT_SIZES {
+        let language = language(dict_size);
+        group.bench_with_input(
+            BenchmarkId::from_parameter(dict_size),
+            &language,
+            |b, language| {
+                b.iter_batched(
+                    || language.clone(),
+                    |language| Translator::builder().language(language).build(),
+                    BatchSize::SmallInput,
+                )
+            },
+        );
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_translate, bench_compile);
+criterion_main!(benches);
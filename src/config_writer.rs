@@ -0,0 +1,216 @@
+//! Edits TOML configs in place. Only the edited entries change, the comments,
+//! formatting and key order of the rest of the file are kept as they are.
+//!
+//! ```
+//! use tranclator::config_writer::ConfigWriter;
+//!
+//! let mut config: ConfigWriter = r#"
+//! [[language]]
+//! name = "pirate" # arr
+//! lower-mode = "preserve"
+//! "#
+//! .parse()
+//! .unwrap();
+//!
+//! config.set_dict_entry("pirate", "hello", "ahoy").unwrap();
+//!
+//! assert!(config.to_string().ends_with("\n[language.dict]\nhello = \"ahoy\"\n"));
+//! ```
+
+use std::str::FromStr;
+use toml_edit::{DocumentMut, Item, Table, TomlError, Value};
+
+/// A TOML config being edited.
+#[derive(Debug, Clone)]
+pub struct ConfigWriter {
+    document: DocumentMut,
+}
+
+/// Why an edit to a config could not be made.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EditError {
+    /// The config does not define a language with this name.
+    UnknownLanguage(String),
+    /// The language is written in a way that can't be edited, such as a
+    /// dictionary that isn't a table.
+    Unsupported(String),
+}
+
+impl std::error::Error for EditError {}
+
+impl std::fmt::Display for EditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EditError::UnknownLanguage(name) => {
+                write!(f, "the config does not define language `{name}`")
+            }
+            EditError::Unsupported(name) => {
+                write!(
+                    f,
+                    "language `{name}` is written in a way that can't be edited"
+                )
+            }
+        }
+    }
+}
+
+impl FromStr for ConfigWriter {
+    type Err = TomlError;
+
+    fn from_str(contents: &str) -> Result<Self, TomlError> {
+        Ok(Self {
+            document: contents.parse()?,
+        })
+    }
+}
+
+impl std::fmt::Display for ConfigWriter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.document.fmt(f)
+    }
+}
+
+impl ConfigWriter {
+    /// Sets the translation of `key` in the dictionary of the first language
+    /// called `language`. An existing entry keeps its place and comment, a new
+    /// one is added at the end, creating a `[language.dict]` table if there is
+    /// no dictionary yet.
+    pub fn set_dict_entry(
+        &mut self,
+        language: &str,
+        key: &str,
+        translation: &str,
+    ) -> Result<(), EditError> {
+        match self
+            .language_mut(language)?
+            .entry("dict")
+            .or_insert_with(toml_edit::table)
+        {
+            Item::Table(dict) => match dict.get_mut(key) {
+                Some(Item::Value(value)) => replace_value(value, translation),
+                _ => {
+                    dict.insert(key, toml_edit::value(translation));
+                }
+            },
+            Item::Value(Value::InlineTable(dict)) => {
+                match dict.get_mut(key) {
+                    Some(value) => replace_value(value, translation),
+                    None => {
+                        dict.insert(key, translation.into());
+                    }
+                }
+                dict.fmt();
+            }
+            _ => return Err(EditError::Unsupported(language.to_string())),
+        }
+
+        Ok(())
+    }
+
+    /// Returns the table of the first language called `name`, which must be
+    /// written as a `[[language]]` table.
+    fn language_mut(&mut self, name: &str) -> Result<&mut Table, EditError> {
+        self.document
+            .get_mut("language")
+            .and_then(Item::as_array_of_tables_mut)
+            .and_then(|languages| {
+                languages
+                    .iter_mut()
+                    .find(|table| table.get("name").and_then(Item::as_str) == Some(name))
+            })
+            .ok_or_else(|| EditError::UnknownLanguage(name.to_string()))
+    }
+}
+
+/// Replaces `value` with `translation`, keeping the whitespace and comment
+/// around it.
+fn replace_value(value: &mut Value, translation: &str) {
+    let decor = value.decor().clone();
+    *value = translation.into();
+    *value.decor_mut() = decor;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"# My languages
+[global]
+default-language = "spanish" # the usual one
+
+[[language]]
+name = "spanish"
+lower-mode = "lower"
+
+[language.dict]
+hello = "hola" # greeting
+cat = ["gato", "minino"]
+
+[[language]]
+name = "dutch"
+lower-mode = "lower"
+dict = { hello = "hallo" }
+
+# Still a work in progress
+[[language]]
+name = "german"
+lower-mode = "lower"
+"#;
+
+    #[test]
+    fn test_set_dict_entry() {
+        let mut config: ConfigWriter = CONFIG.parse().unwrap();
+
+        config.set_dict_entry("spanish", "dog", "perro").unwrap();
+        config.set_dict_entry("spanish", "hello", "buenas").unwrap();
+        config.set_dict_entry("spanish", "cat", "gato").unwrap();
+        config.set_dict_entry("dutch", "cat", "kat").unwrap();
+        config.set_dict_entry("german", "cat", "Katze").unwrap();
+
+        assert_eq!(
+            config.to_string(),
+            r#"# My languages
+[global]
+default-language = "spanish" # the usual one
+
+[[language]]
+name = "spanish"
+lower-mode = "lower"
+
+[language.dict]
+hello = "buenas" # greeting
+cat = "gato"
+dog = "perro"
+
+[[language]]
+name = "dutch"
+lower-mode = "lower"
+dict = { hello = "hallo", cat = "kat" }
+
+# Still a work in progress
+[[language]]
+name = "german"
+lower-mode = "lower"
+
+[language.dict]
+cat = "Katze"
+"#
+        );
+    }
+
+    #[test]
+    fn test_edit_errors() {
+        let mut config: ConfigWriter = CONFIG.parse().unwrap();
+        assert_eq!(
+            config.set_dict_entry("french", "cat", "chat"),
+            Err(EditError::UnknownLanguage("french".to_string()))
+        );
+
+        let mut config: ConfigWriter = "[[language]]\nname = \"x\"\ndict = 1\n".parse().unwrap();
+        assert_eq!(
+            config.set_dict_entry("x", "cat", "kat"),
+            Err(EditError::Unsupported("x".to_string()))
+        );
+        assert_eq!(config.to_string(), "[[language]]\nname = \"x\"\ndict = 1\n");
+    }
+}
@@ -0,0 +1,3904 @@
+//! The translation engine of tranclator: configs, the languages they define
+//! and the translation of text with them. The `tranclator` binary is a command
+//! line interface on top of this library. To translate more than a single
+//! text, build a [`Translator`].
+//!
+//! ```
+//! use tranclator::{Config, translate};
+//!
+//! let config: Config = toml::from_str(
+//!     r#"
+//!     [[language]]
+//!     name = "pirate"
+//!     lower-mode = "preserve"
+//!
+//!     [language.dict]
+//!     hello = "ahoy"
+//!     friend = "matey"
+//!     "#,
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(translate("Hello, friend!", &config.languages[0]), "Ahoy, matey!");
+//! ```
+
+pub mod config_writer;
+
+use aho_corasick::{AhoCorasick, MatchKind};
+use clap::ValueEnum;
+use indexmap::map::IndexMap;
+use log::warn;
+use rand::rngs::StdRng;
+use rand::seq::IndexedRandom;
+use rand::{Rng, SeedableRng};
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::cell::OnceCell;
+use std::collections::{HashMap, HashSet};
+use std::io::ErrorKind;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::LazyLock;
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Translates `text` with the dictionary of `language`. Where a key has
+/// alternative translations, one is picked at random.
+pub fn translate(text: &str, language: &Language) -> String {
+    translate_matches(text, language, &mut rand::rng()).0
+}
+
+/// Translates texts with one language, compiling its dictionary only once.
+///
+/// ```
+/// use tranclator::{Language, Translator};
+///
+/// let language = Language {
+///     name: "pirate".to_string(),
+///     dict: [("hello".to_string(), "ahoy".into())].into_iter().collect(),
+///     ..Default::default()
+/// };
+/// let mut translator = Translator::builder().language(language).seed(1).build().unwrap();
+///
+/// assert_eq!(translator.translate("Hello there"), "Ahoy there");
+/// ```
+#[derive(Debug)]
+pub struct Translator {
+    language: CompiledLanguage,
+    rng: StdRng,
+}
+
+impl Translator {
+    /// Starts building a translator. It needs at least a language.
+    pub fn builder() -> TranslatorBuilder {
+        TranslatorBuilder::default()
+    }
+
+    /// Translates `text`. Alternative translations are picked with the seed
+    /// the translator was built with, if any.
+    pub fn translate(&mut self, text: &str) -> String {
+        self.language.translate_matches(text, &mut self.rng).0
+    }
+
+    /// Returns the language this translates with.
+    pub fn language(&self) -> &Language {
+        &self.language
+    }
+}
+
+/// Collects the settings of a [`Translator`].
+#[derive(Debug, Clone, Default)]
+pub struct TranslatorBuilder {
+    language: Option<Language>,
+    seed: Option<u64>,
+}
+
+impl TranslatorBuilder {
+    /// Sets the language to translate with. This is required.
+    pub fn language(mut self, language: Language) -> Self {
+        self.language = Some(language);
+        self
+    }
+
+    /// Makes the translator pick the same alternative translations on every
+    /// run, instead of picking them at random.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Compiles the language into a [`Translator`].
+    pub fn build(self) -> Result<Translator, BuildError> {
+        let language = self.language.ok_or(BuildError::NoLanguage)?;
+
+        Ok(Translator {
+            language: CompiledLanguage::new(language),
+            rng: match self.seed {
+                Some(seed) => StdRng::seed_from_u64(seed),
+                None => rand::make_rng(),
+            },
+        })
+    }
+}
+
+/// Why a [`Translator`] could not be built.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BuildError {
+    NoLanguage,
+}
+
+impl std::error::Error for BuildError {}
+
+impl std::fmt::Display for BuildError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            BuildError::NoLanguage => f.write_str("No language to translate with was set"),
+        }
+    }
+}
+
+/// The contents of one or more config files.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct Config {
+    pub global: Option<Global>,
+    #[serde(rename = "language", default)]
+    pub languages: Vec<Language>,
+}
+
+/// The `[global]` settings of a config.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub struct Global {
+    pub default_language: Option<String>,
+    pub copy_to_clipboard: Option<bool>,
+    pub quit_keywords: Option<Vec<String>>,
+    pub case_sensitive_quit: Option<bool>,
+    /// The REPL prompt, in which `{language}` and `{mode}` stand for the active
+    /// languages and their capitalization mode.
+    pub repl_prompt: Option<String>,
+}
+
+/// A language as defined in the config: a dictionary and how to translate
+/// with it.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub struct Language {
+    pub name: String,
+    pub lower_mode: CapitalizationMode,
+    #[serde(default)]
+    pub dict: IndexMap<String, Translation>,
+    /// CSV files with more entries, relative to the config file. Each row is
+    /// a key followed by its alternative translations, rows without any are
+    /// skipped. A key in a later file overrides the same key in an earlier
+    /// one, and entries in `dict` override them all. An overridden key keeps
+    /// its place in the dictionary, which matters for keys of the same length.
+    #[serde(alias = "dict-file", default, deserialize_with = "one_or_many")]
+    pub dict_files: Vec<String>,
+    /// What separates the columns of `dict_files`: a tab for `.tsv` files and
+    /// a comma otherwise, unless set.
+    pub dict_delimiter: Option<char>,
+    /// Short for a `match_mode` of [`MatchMode::Whole`], which takes
+    /// precedence when it is set.
+    #[serde(default)]
+    pub whole_word: bool,
+    /// Where in a word a key may match, see [`Language::match_mode`].
+    pub match_mode: Option<MatchMode>,
+    pub copy_to_clipboard: Option<bool>,
+    /// Whether keys match regardless of case, independent of `lower_mode`.
+    #[serde(default = "default_true")]
+    pub match_case_insensitive: bool,
+    /// The dictionary to translate back with, for when inverting `dict` would
+    /// get it wrong.
+    pub reverse_dict: Option<IndexMap<String, String>>,
+    /// Words that are never translated, such as names. They are copied as
+    /// they are wherever they are a whole word.
+    #[serde(default)]
+    pub protect: Vec<String>,
+    /// Lines that start with this, after any indentation, are copied as they
+    /// are, such as comments in a config file.
+    pub passthrough_prefix: Option<String>,
+    /// Whether format placeholders like `{name}`, `{{name}}`, `%s` and `%d`
+    /// are copied as they are, even when a key matches inside them.
+    #[serde(default)]
+    pub protect_placeholders: bool,
+    /// Put before every translation as it is, after capitalizing it.
+    pub output_prefix: Option<String>,
+    /// Put after every translation as it is, after capitalizing it.
+    pub output_suffix: Option<String>,
+    /// What the digits 0 to 9 are written as, in order. Only digits that are
+    /// not part of a match are replaced.
+    pub digits: Option<[String; 10]>,
+    /// Whether keys match regardless of accents, so `café` matches the key
+    /// `cafe`. Keys that are patterns still match the text as it is.
+    #[serde(default)]
+    pub fold_diacritics: bool,
+}
+
+impl Language {
+    /// Where in a word keys match: the `match_mode`, or whole words if
+    /// `whole_word` is set and anywhere otherwise.
+    pub fn match_mode(&self) -> MatchMode {
+        match self.match_mode {
+            Some(mode) => mode,
+            None if self.whole_word => MatchMode::Whole,
+            None => MatchMode::Substring,
+        }
+    }
+}
+
+impl Default for Language {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            lower_mode: CapitalizationMode::default(),
+            dict: IndexMap::new(),
+            dict_files: Vec::new(),
+            dict_delimiter: None,
+            whole_word: false,
+            match_mode: None,
+            copy_to_clipboard: None,
+            match_case_insensitive: true,
+            reverse_dict: None,
+            protect: Vec::new(),
+            passthrough_prefix: None,
+            protect_placeholders: false,
+            output_prefix: None,
+            output_suffix: None,
+            digits: None,
+            fold_diacritics: false,
+        }
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// What a dictionary key translates to: either a single text, a list of
+/// alternatives of which one is picked at random for every occurrence, or an
+/// [`Entry`] with extra settings.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum Translation {
+    Single(String),
+    Choice(Vec<String>),
+    Entry(Entry),
+}
+
+/// A dictionary value written as a table, for entries that need more than
+/// their translation.
+#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case", deny_unknown_fields)]
+pub struct Entry {
+    #[serde(deserialize_with = "one_or_many")]
+    pub translation: Vec<String>,
+    /// Entries with a higher priority are tried first, before longer keys.
+    #[serde(default)]
+    pub priority: i32,
+    /// Documents the entry. It is shown by `show-dict` and otherwise ignored.
+    pub note: Option<String>,
+    /// The entry only applies where the nearest word before the key is this
+    /// one, like `river` before `bank`.
+    pub before: Option<String>,
+    /// The entry only applies where the nearest word after the key is this
+    /// one.
+    pub after: Option<String>,
+    /// What the key translates to where `before` or `after` doesn't hold,
+    /// which may have guards of its own. Without it, the key doesn't match
+    /// there at all.
+    pub otherwise: Option<Box<Translation>>,
+    /// The key only matches text written with the same case, even if the
+    /// language matches regardless of case. Like a guard, `otherwise` applies
+    /// where it doesn't.
+    #[serde(default)]
+    pub case_sensitive: bool,
+}
+
+/// Deserializes either a single string or a list of strings into a list.
+fn one_or_many<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<String>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(String),
+        Many(Vec<String>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(text) => vec![text],
+        OneOrMany::Many(texts) => texts,
+    })
+}
+
+impl Translation {
+    /// Returns every text this can translate to.
+    pub fn alternatives(&self) -> &[String] {
+        match self {
+            Translation::Single(text) => std::slice::from_ref(text),
+            Translation::Choice(texts) => texts,
+            Translation::Entry(entry) => &entry.translation,
+        }
+    }
+
+    pub fn priority(&self) -> i32 {
+        match self {
+            Translation::Entry(entry) => entry.priority,
+            _ => 0,
+        }
+    }
+
+    pub fn note(&self) -> Option<&str> {
+        match self {
+            Translation::Entry(entry) => entry.note.as_deref(),
+            _ => None,
+        }
+    }
+
+    /// Picks one of the alternatives, or the empty string if there are none.
+    pub fn pick(&self, rng: &mut dyn Rng) -> &str {
+        self.alternatives().choose(rng).map_or("", String::as_str)
+    }
+}
+
+impl From<&str> for Translation {
+    fn from(text: &str) -> Self {
+        Translation::Single(text.to_string())
+    }
+}
+
+impl std::fmt::Display for Translation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.alternatives().join(" | "))
+    }
+}
+
+/// Where in a word a dictionary key may match.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum MatchMode {
+    /// Anywhere, even inside a longer word.
+    #[default]
+    Substring,
+    /// Only a whole word or run of words, as defined by Unicode.
+    Whole,
+    /// At the start of a word, keeping the rest of the word, so `cat` turns
+    /// `cats` into `gatos`. The longest key still wins, so `cats` would match
+    /// all of `cats` before `cat` does.
+    Prefix,
+}
+
+/// How a language cases its translations.
+#[derive(Deserialize, ValueEnum, Debug, Clone, PartialEq, Eq, Hash, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum CapitalizationMode {
+    Lower,
+    #[default]
+    Preserve,
+    Upper,
+    Title,
+    Sentence,
+}
+
+impl std::fmt::Display for CapitalizationMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            CapitalizationMode::Lower => "lower",
+            CapitalizationMode::Preserve => "preserve",
+            CapitalizationMode::Upper => "upper",
+            CapitalizationMode::Title => "title",
+            CapitalizationMode::Sentence => "sentence",
+        })
+    }
+}
+
+/// Translates `text` through every language of `chain` in turn, feeding the
+/// output of each into the next.
+pub fn translate_chain(text: &str, chain: &[CompiledLanguage], rng: &mut dyn Rng) -> String {
+    translate_chain_spans(text, chain, rng).0
+}
+
+/// Translates `text` like [`translate_chain`], also returning where the
+/// substitutions of the last language ended up in the translated text.
+pub fn translate_chain_spans(
+    text: &str,
+    chain: &[CompiledLanguage],
+    rng: &mut dyn Rng,
+) -> (String, Vec<Range<usize>>) {
+    let (translated, substitutions) = translate_chain_matches(text, chain, rng);
+    let spans = substitutions.into_iter().map(|s| s.target).collect();
+
+    (translated, spans)
+}
+
+/// Translates `text` like [`translate_chain`], also returning the
+/// substitutions made by the last language. Their source ranges refer to the
+/// text that language was given.
+pub fn translate_chain_matches(
+    text: &str,
+    chain: &[CompiledLanguage],
+    rng: &mut dyn Rng,
+) -> (String, Vec<Substitution>) {
+    let Some((last, stages)) = chain.split_last() else {
+        return (trim_blank_lines(text).to_string(), Vec::new());
+    };
+
+    let (text, made) = translate_chain_counted(text, stages, rng);
+    last.translate_matches_after(&text, made, rng)
+}
+
+/// Translates `text` like [`translate_chain`], also returning how many
+/// replacements the languages made together. Every language counts the
+/// replacements of the languages before it against its maximum, so a chain
+/// makes no more replacements than any one of them would.
+pub fn translate_chain_counted(
+    text: &str,
+    chain: &[CompiledLanguage],
+    rng: &mut dyn Rng,
+) -> (String, usize) {
+    chain.iter().fold(
+        (trim_blank_lines(text).to_string(), 0),
+        |(text, made), language| {
+            let (text, substitutions) = language.translate_matches_after(&text, made, rng);
+            (text, made + substitutions.len())
+        },
+    )
+}
+
+/// Translates only the `fragments` of `text` through every language of
+/// `chain` in turn, see [`CompiledLanguage::translate_fragments`]. Like
+/// [`translate_chain_counted`], the languages share their maximum number of
+/// replacements.
+pub fn translate_chain_fragments(
+    text: &str,
+    fragments: &[Range<usize>],
+    chain: &[CompiledLanguage],
+    rng: &mut dyn Rng,
+) -> String {
+    let text = trim_blank_lines(text);
+    let mut fragments = fragments.to_vec();
+    let mut result = text.to_string();
+    let mut made = 0;
+
+    for language in chain {
+        let count;
+        (result, fragments, count) = language.translate_fragments(&result, &fragments, made, rng);
+        made += count;
+    }
+
+    result
+}
+
+/// Why a word was translated or left as it is, see
+/// [`CompiledLanguage::explain`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Explanation {
+    /// The word is part of a match of this dictionary key.
+    Matched(String),
+    /// The word is copied as it is, being a protected word or part of an
+    /// escape, placeholder or passthrough line.
+    Protected,
+    /// This key matches the word on its own, but an overlapping match, a
+    /// protected span or the maximum number of replacements came first.
+    Outranked(String),
+    /// This key matches the word, but the words around it don't satisfy its
+    /// `before` or `after` guards, nor those of an `otherwise`.
+    Guarded(String),
+    /// No dictionary key matches the word.
+    NoKey,
+}
+
+/// A single dictionary replacement made while translating.
+#[derive(Serialize, Debug, Clone, PartialEq, Eq)]
+pub struct Substitution {
+    /// The dictionary key that matched.
+    pub key: String,
+    /// Where the match was found in the trimmed input.
+    pub source: Range<usize>,
+    /// Where the replacement ended up in the translated text.
+    pub target: Range<usize>,
+}
+
+/// Translates `text` with the dictionary of `language`, picking between
+/// alternative translations with `rng`. Also returns every substitution that
+/// was made, in order.
+pub fn translate_matches(
+    text: &str,
+    language: &Language,
+    rng: &mut dyn Rng,
+) -> (String, Vec<Substitution>) {
+    CompiledLanguage::new(language.clone()).translate_matches(text, rng)
+}
+
+/// A language with its dictionary compiled for matching: the entries sorted in
+/// the order they are tried and an automaton finding all literal keys at once.
+/// Compiling is the costly part of translating a short text, so a language
+/// that translates many texts is compiled once and kept.
+#[derive(Debug, Clone)]
+pub struct CompiledLanguage {
+    language: Language,
+    /// Every entry in the order it is tried, with the index of its key in the
+    /// dictionary. Empty keys and invalid patterns are left out.
+    entries: Vec<(Matcher, usize)>,
+    /// Finds every literal key. Pattern `i` of it is the entry `literals[i]`.
+    automaton: AhoCorasick,
+    literals: Vec<usize>,
+    /// The entries whose key is a regular expression.
+    patterns: Vec<usize>,
+    /// Finds the protected words.
+    protect: AhoCorasick,
+    /// How many replacements one translation may make at most.
+    max_replacements: Option<usize>,
+}
+
+impl CompiledLanguage {
+    pub fn new(language: Language) -> Self {
+        let entries: Vec<(Matcher, usize)> = sorted_entries(&language.dict)
+            .into_iter()
+            .filter_map(|(key, _)| {
+                let matcher = match key.strip_prefix(PATTERN_PREFIX) {
+                    Some(pattern) => {
+                        match build_pattern(pattern, language.match_case_insensitive) {
+                            Ok(regex) => Matcher::Pattern(regex),
+                            Err(e) => {
+                                warn!("Ignoring invalid pattern `{pattern}`: {e}");
+                                return None;
+                            }
+                        }
+                    }
+                    None => {
+                        let (word, at_start, at_end) = strip_anchors(key);
+                        Matcher::Literal {
+                            word: fold_for_matching(&language, word).0,
+                            at_start,
+                            at_end,
+                        }
+                    }
+                };
+
+                Some((matcher, language.dict.get_index_of(key)?))
+            })
+            .filter(
+                |(matcher, _)| !matches!(matcher, Matcher::Literal { word, .. } if word.is_empty()),
+            )
+            .collect();
+
+        let literals: Vec<usize> = entries
+            .iter()
+            .enumerate()
+            .filter(|(_, (matcher, _))| matches!(matcher, Matcher::Literal { .. }))
+            .map(|(i, _)| i)
+            .collect();
+
+        // Matches may overlap, so that a key that is passed over, for example
+        // because it is not a whole word, can fall back to a shorter one.
+        let automaton = AhoCorasick::builder()
+            .match_kind(MatchKind::Standard)
+            .build(literals.iter().map(|&i| match &entries[i].0 {
+                Matcher::Literal { word, .. } => word,
+                Matcher::Pattern(_) => unreachable!("only literal entries are in `literals`"),
+            }))
+            .expect("dictionary keys should fit in an automaton");
+
+        let patterns = entries
+            .iter()
+            .enumerate()
+            .filter(|(_, (matcher, _))| matches!(matcher, Matcher::Pattern(_)))
+            .map(|(i, _)| i)
+            .collect();
+
+        let protect = AhoCorasick::builder()
+            .match_kind(MatchKind::LeftmostLongest)
+            .build(
+                language
+                    .protect
+                    .iter()
+                    .map(|word| fold_for_matching(&language, word).0)
+                    .filter(|word| !word.is_empty()),
+            )
+            .expect("protected words should fit in an automaton");
+
+        CompiledLanguage {
+            language,
+            entries,
+            automaton,
+            literals,
+            patterns,
+            protect,
+            max_replacements: None,
+        }
+    }
+
+    /// Stops every translation after `max` replacements, leaving the rest of
+    /// the text untranslated. With `None`, there is no limit.
+    pub fn with_max_replacements(mut self, max: Option<usize>) -> Self {
+        self.max_replacements = max;
+        self
+    }
+
+    /// Translates `text` like [`translate_matches`], without compiling the
+    /// dictionary again.
+    pub fn translate_matches(&self, text: &str, rng: &mut dyn Rng) -> (String, Vec<Substitution>) {
+        self.translate_matches_after(text, 0, rng)
+    }
+
+    /// Translates `text` like [`Self::translate_matches`], counting the `made`
+    /// replacements of the languages before this one in a chain against its
+    /// maximum.
+    pub fn translate_matches_after(
+        &self,
+        text: &str,
+        made: usize,
+        rng: &mut dyn Rng,
+    ) -> (String, Vec<Substitution>) {
+        let (mut result, mut substitutions, verbatim) =
+            self.substitute(trim_blank_lines(text), made, rng);
+        self.finish(
+            &mut result,
+            &verbatim,
+            substitutions.iter_mut().map(|s| &mut s.target).collect(),
+        );
+
+        (result, substitutions)
+    }
+
+    /// Translates only the `fragments` of `text`, sorted byte ranges that
+    /// don't overlap, copying the rest as it is. Unlike translating every
+    /// fragment on its own, the capitalization mode and the output prefix and
+    /// suffix apply once, to the whole text. Counts `made` replacements
+    /// against the maximum like [`Self::translate_matches_after`]. Returns the
+    /// translation with where each fragment ended up in it and how many
+    /// replacements it made.
+    pub fn translate_fragments(
+        &self,
+        text: &str,
+        fragments: &[Range<usize>],
+        made: usize,
+        rng: &mut dyn Rng,
+    ) -> (String, Vec<Range<usize>>, usize) {
+        let mut made_here = 0;
+        let mut result = String::with_capacity(text.len());
+        let mut verbatim = Vec::new();
+        let mut translated = Vec::with_capacity(fragments.len());
+        let copy = |result: &mut String, verbatim: &mut Vec<Range<usize>>, s: &str| {
+            let start = result.len();
+            result.push_str(s);
+            verbatim.push(start..result.len());
+        };
+        let mut last = 0;
+
+        for fragment in fragments {
+            copy(&mut result, &mut verbatim, &text[last..fragment.start]);
+            let start = result.len();
+
+            // Blank lines around the fragment are copied, like translating
+            // trims them.
+            let kept = trimmed_range(&text[fragment.clone()]);
+            let kept = fragment.start + kept.start..fragment.start + kept.end;
+            copy(
+                &mut result,
+                &mut verbatim,
+                &text[fragment.start..kept.start],
+            );
+
+            let offset = result.len();
+            let (part, substitutions, part_verbatim) =
+                self.substitute(&text[kept.clone()], made + made_here, rng);
+            made_here += substitutions.len();
+            result.push_str(&part);
+            verbatim.extend(
+                part_verbatim
+                    .into_iter()
+                    .map(|range| range.start + offset..range.end + offset),
+            );
+
+            copy(&mut result, &mut verbatim, &text[kept.end..fragment.end]);
+            translated.push(start..result.len());
+            last = fragment.end;
+        }
+
+        copy(&mut result, &mut verbatim, &text[last..]);
+        self.finish(&mut result, &verbatim, translated.iter_mut().collect());
+
+        (result, translated, made_here)
+    }
+
+    /// Replaces the matches in `text`, which is already trimmed, casing them
+    /// and the text between them but leaving the title and sentence casing
+    /// and the output prefix and suffix to [`Self::finish`]. Counts `made`
+    /// replacements against the maximum. Returns the result with its
+    /// substitutions and the ranges of it that were copied as they are.
+    fn substitute(
+        &self,
+        text: &str,
+        made: usize,
+        rng: &mut dyn Rng,
+    ) -> (String, Vec<Substitution>, Vec<Range<usize>>) {
+        let language = &self.language;
+
+        // Text between matches is kept as-is only when preserving case. Its
+        // digits are replaced after folding, so they are written as given.
+        let unmatched = |s: &str| {
+            let s = match language.lower_mode {
+                CapitalizationMode::Preserve => s.to_string(),
+                _ => fold_case(&language.lower_mode, s),
+            };
+
+            match &language.digits {
+                Some(digits) => replace_digits(&s, digits),
+                None => s,
+            }
+        };
+
+        let mut result = String::with_capacity(text.len());
+        let mut substitutions = Vec::new();
+        let mut verbatim = Vec::new();
+        let mut last = 0;
+
+        for segment in self.segments(text, Some(made)) {
+            let (i, Range { start: pos, end }) = match segment {
+                Segment::Match(i, range) => (i, range),
+                Segment::Protected(span, copied) => {
+                    result.push_str(&unmatched(&text[last..span.start]));
+                    let start = result.len();
+                    result.push_str(&text[copied]);
+                    verbatim.push(start..result.len());
+                    last = span.end;
+                    continue;
+                }
+            };
+
+            let (matcher, _) = &self.entries[i];
+            let key = self.key(i);
+            let mut translation = self
+                .translation_at(i, text, pos..end)
+                .expect("segments only match entries that apply")
+                .pick(rng)
+                .to_string();
+
+            if let Matcher::Pattern(regex) = matcher
+                && let Some(captures) = regex.captures_at(text, pos)
+            {
+                let mut expanded = String::new();
+                captures.expand(&translation, &mut expanded);
+                translation = expanded;
+            }
+
+            let replacement = match language.lower_mode {
+                CapitalizationMode::Lower
+                | CapitalizationMode::Title
+                | CapitalizationMode::Sentence => translation.to_lowercase(),
+                CapitalizationMode::Upper => translation.to_uppercase(),
+                CapitalizationMode::Preserve => {
+                    let segment = &text[pos..end];
+
+                    // A single capital can't tell a shouted word from a capitalized
+                    // one, so look at the whole word around it instead.
+                    let original = if segment.chars().filter(|c| c.is_alphabetic()).count() == 1 {
+                        enclosing_word(text, pos..end)
+                    } else {
+                        segment
+                    };
+
+                    apply_case(original, &translation)
+                }
+            };
+
+            result.push_str(&unmatched(&text[last..pos]));
+            let start = result.len();
+            result.push_str(&replacement);
+            substitutions.push(Substitution {
+                key: key.to_string(),
+                source: pos..end,
+                target: start..result.len(),
+            });
+            last = end;
+        }
+
+        result.push_str(&unmatched(&text[last..]));
+
+        (result, substitutions, verbatim)
+    }
+
+    /// Applies the title or sentence casing of the language to `result`, apart
+    /// from its sorted `verbatim` ranges, and puts the output prefix and
+    /// suffix around it. The `targets`, ranges of `result`, move along.
+    fn finish(
+        &self,
+        result: &mut String,
+        verbatim: &[Range<usize>],
+        mut targets: Vec<&mut Range<usize>>,
+    ) {
+        let language = &self.language;
+
+        match language.lower_mode {
+            CapitalizationMode::Title => {
+                *result = map_chars(result, verbatim, &mut targets, title_case());
+            }
+            CapitalizationMode::Sentence => {
+                *result = map_chars(result, verbatim, &mut targets, sentence_case());
+            }
+            _ => {}
+        }
+
+        if !result.is_empty()
+            && let Some(prefix) = &language.output_prefix
+        {
+            result.insert_str(0, prefix);
+            for target in targets {
+                *target = target.start + prefix.len()..target.end + prefix.len();
+            }
+        }
+
+        if !result.is_empty()
+            && let Some(suffix) = &language.output_suffix
+        {
+            result.push_str(suffix);
+        }
+    }
+
+    /// Counts the substitutions translating `text` makes, without building the
+    /// translation.
+    pub fn count_matches(&self, text: &str) -> usize {
+        self.segments(trim_blank_lines(text), Some(0))
+            .iter()
+            .filter(|segment| matches!(segment, Segment::Match(..)))
+            .count()
+    }
+
+    /// Returns the dictionary key of every substitution translating `text`
+    /// makes, in order.
+    pub fn matched_keys(&self, text: &str) -> Vec<&str> {
+        self.segments(trim_blank_lines(text), Some(0))
+            .into_iter()
+            .filter_map(|segment| match segment {
+                Segment::Match(i, _) => Some(self.key(i)),
+                Segment::Protected(..) => None,
+            })
+            .collect()
+    }
+
+    /// Returns what entry `i` translates its match `span` of `text` to: its
+    /// translation, or else the first `otherwise` whose guards hold there.
+    /// Returns `None` if there is none, so the entry doesn't apply.
+    fn translation_at(&self, i: usize, text: &str, span: Range<usize>) -> Option<&Translation> {
+        let (_, mut translation) = self
+            .language
+            .dict
+            .get_index(self.entries[i].1)
+            .expect("entries index the dictionary");
+
+        loop {
+            let Translation::Entry(entry) = translation else {
+                return Some(translation);
+            };
+
+            if (!entry.case_sensitive || self.matches_case(i, &text[span.clone()]))
+                && self.guard_holds(
+                    entry.before.as_deref(),
+                    text[..span.start].split_word_bounds().rev(),
+                )
+                && self.guard_holds(entry.after.as_deref(), text[span.end..].split_word_bounds())
+            {
+                return Some(translation);
+            }
+
+            translation = entry.otherwise.as_deref()?;
+        }
+    }
+
+    /// Returns whether entry `i` translates `matched` wherever it appears,
+    /// ignoring the guards of the entry and its `otherwise`s.
+    fn applies_unguarded(&self, i: usize, matched: &str) -> bool {
+        let (_, mut translation) = self
+            .language
+            .dict
+            .get_index(self.entries[i].1)
+            .expect("entries index the dictionary");
+
+        loop {
+            let Translation::Entry(entry) = translation else {
+                return true;
+            };
+
+            if !entry.case_sensitive || self.matches_case(i, matched) {
+                return true;
+            }
+
+            let Some(otherwise) = entry.otherwise.as_deref() else {
+                return false;
+            };
+            translation = otherwise;
+        }
+    }
+
+    /// Returns whether `matched` is written with the same case as the key of
+    /// entry `i`. Patterns match the text as it is, so they always do.
+    fn matches_case(&self, i: usize, matched: &str) -> bool {
+        match &self.entries[i].0 {
+            Matcher::Literal { .. } => {
+                let (word, _, _) = strip_anchors(self.key(i));
+                let fold = |s: &str| fold_with_case(&self.language, s, false).0;
+                fold(matched) == fold(word)
+            }
+            Matcher::Pattern(_) => true,
+        }
+    }
+
+    /// Returns whether there is no `guard` or the first word of `tokens` is
+    /// that guard, compared the way keys are.
+    fn guard_holds<'a>(
+        &self,
+        guard: Option<&str>,
+        mut tokens: impl Iterator<Item = &'a str>,
+    ) -> bool {
+        let Some(guard) = guard else {
+            return true;
+        };
+        let fold = |s: &str| fold_for_matching(&self.language, s).0;
+
+        tokens
+            .find(|token| token.chars().any(char::is_alphanumeric))
+            .is_some_and(|word| fold(word) == fold(guard))
+    }
+
+    /// Returns the dictionary key of entry `i`.
+    fn key(&self, i: usize) -> &str {
+        let (key, _) = self
+            .language
+            .dict
+            .get_index(self.entries[i].1)
+            .expect("entries index the dictionary");
+        key
+    }
+
+    /// Explains for every word of `text` why translating it does or doesn't
+    /// replace that word, in order.
+    pub fn explain<'a>(&self, text: &'a str) -> Vec<(&'a str, Explanation)> {
+        self.explain_after(text, 0)
+    }
+
+    /// Explains `text` like [`Self::explain`], counting the `made`
+    /// replacements of the languages before this one in a chain against its
+    /// maximum.
+    pub fn explain_after<'a>(&self, text: &'a str, made: usize) -> Vec<(&'a str, Explanation)> {
+        let text = trim_blank_lines(text);
+        let segments = self.segments(text, Some(made));
+        let key = |i: usize| self.key(i).to_string();
+
+        tokenize(text)
+            .filter(|token| token.is_word)
+            .map(|token| {
+                let end = token.start + token.text.len();
+                let overlapping = segments.iter().find(|segment| {
+                    let (Segment::Match(_, span) | Segment::Protected(span, _)) = segment;
+                    span.start < end && token.start < span.end
+                });
+
+                let explanation = match overlapping {
+                    Some(Segment::Match(i, _)) => Explanation::Matched(key(*i)),
+                    Some(Segment::Protected(..)) => Explanation::Protected,
+                    None => match self.segments(token.text, None).first() {
+                        Some(Segment::Match(i, span)) if span.len() == token.text.len() => {
+                            if self.translation_at(*i, text, token.start..end).is_some() {
+                                Explanation::Outranked(key(*i))
+                            } else {
+                                Explanation::Guarded(key(*i))
+                            }
+                        }
+                        _ => Explanation::NoKey,
+                    },
+                };
+
+                (token.text, explanation)
+            })
+            .collect()
+    }
+
+    /// Finds what translating `text`, which is already trimmed, replaces and
+    /// what it copies, in order. Counts `made` replacements already made
+    /// against the maximum number. With `None`, ignores the maximum and the
+    /// guards of entries instead, to find what keys match `text` at all.
+    fn segments(&self, text: &str, made: Option<usize>) -> Vec<Segment> {
+        let language = &self.language;
+        let (haystack, origins) = fold_for_matching(language, text);
+
+        // Word boundaries are only needed for match modes other than substring
+        // and for anchored keys.
+        let boundaries = OnceCell::new();
+        let is_boundary = |i: usize| boundaries.get_or_init(|| word_boundaries(text))[i];
+        let match_mode = language.match_mode();
+
+        let mut offsets = vec![None; text.len() + 1];
+        for (folded_pos, origin) in origins.iter().enumerate() {
+            if let Some(pos) = origin {
+                offsets[*pos] = Some(folded_pos);
+            }
+        }
+
+        // The spans that are copied instead of translated, with the part of
+        // them that is copied: passthrough lines, the inside of `{{...}}`
+        // escapes, placeholders and protected words that are whole words.
+        // Spans that overlap an earlier kind are left out. With placeholders
+        // protected, `{{...}}` is a placeholder too and keeps its braces.
+        let mut protected: Vec<(Range<usize>, Range<usize>)> =
+            passthrough_lines(text, language.passthrough_prefix.as_deref())
+                .map(|line| (line.clone(), line))
+                .collect();
+        let overlaps = |protected: &[(Range<usize>, Range<usize>)], span: &Range<usize>| {
+            protected
+                .iter()
+                .any(|(other, _)| other.start < span.end && span.start < other.end)
+        };
+
+        for (span, inside) in escapes(text) {
+            if !overlaps(&protected, &span) {
+                let copied = if language.protect_placeholders {
+                    span.clone()
+                } else {
+                    inside
+                };
+                protected.push((span, copied));
+            }
+        }
+
+        if language.protect_placeholders {
+            for placeholder in placeholders(text) {
+                if !overlaps(&protected, &placeholder) {
+                    protected.push((placeholder.clone(), placeholder));
+                }
+            }
+        }
+
+        let words: Vec<_> = self
+            .protect
+            .find_iter(&haystack)
+            .filter_map(|m| Some(origins[m.start()]?..origins[m.end()]?))
+            .filter(|span| is_boundary(span.start) && is_boundary(span.end))
+            .filter(|span| !overlaps(&protected, span))
+            .map(|span| (span.clone(), span))
+            .collect();
+        protected.extend(words);
+        protected.sort_unstable_by_key(|(span, _)| span.start);
+        let mut next_protected = 0;
+
+        // The start, entry and end of every literal match in the haystack,
+        // found in a single pass and ordered so the entries found at a
+        // position are in the order they are tried.
+        let mut literal_matches: Vec<(usize, usize, usize)> = self
+            .automaton
+            .find_overlapping_iter(&haystack)
+            .map(|m| (m.start(), self.literals[m.pattern().as_usize()], m.end()))
+            .collect();
+        literal_matches.sort_unstable();
+        let mut next_literal = 0;
+
+        // The next match of every pattern at or after the current position, so a
+        // pattern only searches the text again once the scan has passed it.
+        let mut next_matches: Vec<Option<Option<Range<usize>>>> = vec![None; self.entries.len()];
+
+        // The entries that may match at the current position, with the end of the
+        // match in the haystack for literal keys.
+        let mut candidates: Vec<(usize, usize)> = Vec::new();
+
+        // The text is scanned once from left to right, so a match never
+        // overlaps an earlier one.
+        let mut segments = Vec::new();
+        let mut matches = made.unwrap_or(0);
+        let mut capped = false;
+        let mut pos = 0;
+
+        while let Some(c) = text[pos..].chars().next() {
+            if let Some((span, copied)) = protected.get(next_protected)
+                && span.start == pos
+            {
+                segments.push(Segment::Protected(span.clone(), copied.clone()));
+                next_protected += 1;
+                pos = span.end;
+                continue;
+            }
+
+            // Matches may not reach into the next protected span.
+            let limit = protected
+                .get(next_protected)
+                .map_or(text.len(), |(span, _)| span.start);
+            // Nothing matches from a char that folding left out, such as a
+            // combining mark.
+            let Some(folded_pos) = offsets[pos] else {
+                pos += c.len_utf8();
+                continue;
+            };
+            let mut found = None;
+
+            while literal_matches
+                .get(next_literal)
+                .is_some_and(|m| m.0 < folded_pos)
+            {
+                next_literal += 1;
+            }
+
+            candidates.clear();
+            candidates.extend(
+                literal_matches[next_literal..]
+                    .iter()
+                    .take_while(|m| m.0 == folded_pos)
+                    .map(|&(_, i, end)| (i, end)),
+            );
+            candidates.extend(self.patterns.iter().map(|&i| (i, 0)));
+            candidates.sort_unstable();
+
+            for &(i, folded_end) in &candidates {
+                let end = match &self.entries[i].0 {
+                    Matcher::Literal {
+                        at_start, at_end, ..
+                    } => {
+                        // Matches may only end where a char of `text` does.
+                        let Some(end) = origins[folded_end] else {
+                            continue;
+                        };
+
+                        if (*at_start && !is_boundary(pos)) || (*at_end && !is_boundary(end)) {
+                            continue;
+                        }
+
+                        end
+                    }
+                    Matcher::Pattern(regex) => {
+                        let next = &mut next_matches[i];
+                        if next
+                            .as_ref()
+                            .is_none_or(|m| m.as_ref().is_some_and(|m| m.start < pos))
+                        {
+                            *next = Some(regex.find_at(text, pos).map(|m| m.range()));
+                        }
+
+                        match next {
+                            Some(Some(m)) if m.start == pos && m.end > pos => m.end,
+                            _ => continue,
+                        }
+                    }
+                };
+
+                let at_boundaries = match match_mode {
+                    MatchMode::Substring => true,
+                    MatchMode::Whole => is_boundary(pos) && is_boundary(end),
+                    MatchMode::Prefix => is_boundary(pos),
+                };
+
+                let applies = match made {
+                    Some(_) => self.translation_at(i, text, pos..end).is_some(),
+                    None => self.applies_unguarded(i, &text[pos..end]),
+                };
+
+                if end <= limit && at_boundaries && applies {
+                    found = Some((i, end));
+                    break;
+                }
+            }
+
+            if found.is_some()
+                && made.is_some()
+                && let Some(max) = self.max_replacements
+                && matches >= max
+            {
+                if !capped {
+                    warn!("Stopped translating after the maximum of {max} replacements");
+                    capped = true;
+                }
+
+                found = None;
+            }
+
+            let Some((i, end)) = found else {
+                pos += c.len_utf8();
+                continue;
+            };
+
+            segments.push(Segment::Match(i, pos..end));
+            matches += 1;
+            pos = end;
+        }
+
+        segments
+    }
+}
+
+/// A part of a text that translating doesn't copy as it is.
+enum Segment {
+    /// Entry `i` of [`CompiledLanguage::entries`] matched the range.
+    Match(usize, Range<usize>),
+    /// The first range is copied untranslated, as the second range.
+    Protected(Range<usize>, Range<usize>),
+}
+
+impl std::ops::Deref for CompiledLanguage {
+    type Target = Language;
+
+    fn deref(&self) -> &Language {
+        &self.language
+    }
+}
+
+/// How a dictionary key is found in the text.
+#[derive(Debug, Clone)]
+enum Matcher {
+    /// The case-folded key, matched literally. It may be anchored to the start
+    /// or end of a word.
+    Literal {
+        word: String,
+        at_start: bool,
+        at_end: bool,
+    },
+    /// A `re:` key, matched as a regular expression.
+    Pattern(Regex),
+}
+
+/// Marks a dictionary key as a regular expression. Its translation may refer
+/// to capture groups as `$1` or `${name}`.
+pub const PATTERN_PREFIX: &str = "re:";
+
+/// Splits the anchors off a literal key, returning the word along with whether
+/// it must start a word (`^cat`) and whether it must end one (`dog$`).
+pub fn strip_anchors(key: &str) -> (&str, bool, bool) {
+    let (key, at_start) = match key.strip_prefix('^') {
+        Some(rest) => (rest, true),
+        None => (key, false),
+    };
+    let (key, at_end) = match key.strip_suffix('$') {
+        Some(rest) => (rest, true),
+        None => (key, false),
+    };
+
+    (key, at_start, at_end)
+}
+
+/// Compiles the `re:` key `pattern`, matching regardless of case if
+/// `case_insensitive` is set.
+fn build_pattern(pattern: &str, case_insensitive: bool) -> Result<Regex, regex::Error> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(case_insensitive)
+        .build()
+}
+
+/// Cases `translation` like `original`: lower case if `original` is, upper case
+/// if `original` is in all caps and has more than one letter, and with its
+/// first letter capitalized otherwise.
+fn apply_case(original: &str, translation: &str) -> String {
+    if original.to_lowercase() == original {
+        translation.to_lowercase()
+    } else if original.to_uppercase() == original
+        && original.chars().filter(|c| c.is_alphabetic()).count() > 1
+    {
+        translation.to_uppercase()
+    } else {
+        let mut c = translation.chars();
+        match c.next() {
+            None => String::new(),
+            Some(f) => f.to_uppercase().chain(c).collect(),
+        }
+    }
+}
+
+/// Returns where every line of `text` that starts with `prefix` after its
+/// indentation is, without its line break. An empty prefix matches no line.
+fn passthrough_lines<'a>(
+    text: &'a str,
+    prefix: Option<&'a str>,
+) -> impl Iterator<Item = Range<usize>> + 'a {
+    let prefix = prefix.filter(|prefix| !prefix.is_empty());
+
+    text.split_inclusive('\n')
+        .scan(0, |start, line| {
+            let range = *start..*start + line.trim_end_matches(['\r', '\n']).len();
+            *start += line.len();
+            Some((range, line))
+        })
+        .filter(move |(_, line)| prefix.is_some_and(|prefix| line.trim_start().starts_with(prefix)))
+        .map(|(range, _)| range)
+}
+
+/// Replaces every ASCII digit in `text` with what `digits` has at its value.
+fn replace_digits(text: &str, digits: &[String; 10]) -> String {
+    let mut result = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c.to_digit(10) {
+            Some(digit) if c.is_ascii_digit() => result.push_str(&digits[digit as usize]),
+            _ => result.push(c),
+        }
+    }
+
+    result
+}
+
+/// Finds every format placeholder in `text`: `{...}` without braces inside
+/// it, `%s` and `%d`.
+fn placeholders(text: &str) -> impl Iterator<Item = Range<usize>> + '_ {
+    static PLACEHOLDER: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"\{[^{}]*\}|%[sd]").expect("the pattern is valid"));
+
+    PLACEHOLDER.find_iter(text).map(|m| m.range())
+}
+
+/// Finds every `{{...}}` escape in `text`, returning where each is along with
+/// where the text inside its braces is.
+fn escapes(text: &str) -> Vec<(Range<usize>, Range<usize>)> {
+    let mut escapes = Vec::new();
+    let mut pos = 0;
+
+    while let Some(open) = text[pos..].find("{{") {
+        let start = pos + open;
+        let Some(close) = text[start + 2..].find("}}") else {
+            break;
+        };
+        let end = start + 2 + close + 2;
+
+        escapes.push((start..end, start + 2..end - 2));
+        pos = end;
+    }
+
+    escapes
+}
+
+/// Returns the run of alphanumeric chars in `text` that contains `range`.
+fn enclosing_word(text: &str, range: Range<usize>) -> &str {
+    let start = text[..range.start]
+        .char_indices()
+        .rev()
+        .take_while(|(_, c)| c.is_alphanumeric())
+        .last()
+        .map_or(range.start, |(i, _)| i);
+    let end = text[range.end..]
+        .char_indices()
+        .find(|(_, c)| !c.is_alphanumeric())
+        .map_or(text.len(), |(i, _)| range.end + i);
+
+    &text[start..end]
+}
+
+/// Rewrites `text` by feeding its chars in order to `map`, which pushes the
+/// replacement of each char to the output. Chars in the sorted `verbatim`
+/// ranges are still fed to `map`, but copied as they are. The `targets`, ranges
+/// of `text`, are moved along with the new offsets.
+fn map_chars(
+    text: &str,
+    verbatim: &[Range<usize>],
+    targets: &mut [&mut Range<usize>],
+    mut map: impl FnMut(char, &mut String),
+) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut offsets = vec![0; text.len() + 1];
+    let mut verbatim = verbatim.iter().peekable();
+    let mut ignored = String::new();
+
+    for (i, c) in text.char_indices() {
+        offsets[i] = result.len();
+
+        while verbatim.next_if(|range| range.end <= i).is_some() {}
+
+        if verbatim.peek().is_some_and(|range| range.contains(&i)) {
+            ignored.clear();
+            map(c, &mut ignored);
+            result.push(c);
+        } else {
+            map(c, &mut result);
+        }
+    }
+
+    offsets[text.len()] = result.len();
+
+    for target in targets {
+        **target = offsets[target.start]..offsets[target.end];
+    }
+
+    result
+}
+
+/// Returns a [`map_chars`] mapping that upper cases the first letter or digit
+/// of the text and of every sentence. A sentence starts after whitespace that
+/// follows a `.`, `!` or `?`, so abbreviations like `e.g.` followed by a space
+/// also start one.
+fn sentence_case() -> impl FnMut(char, &mut String) {
+    let mut start = true;
+    let mut after_terminator = false;
+
+    move |c, out| {
+        if matches!(c, '.' | '!' | '?') {
+            after_terminator = true;
+            out.push(c);
+        } else if c.is_whitespace() {
+            start = start || after_terminator;
+            out.push(c);
+        } else {
+            if start && c.is_alphanumeric() {
+                out.extend(c.to_uppercase());
+                start = false;
+            } else {
+                out.push(c);
+            }
+
+            after_terminator = false;
+        }
+    }
+}
+
+/// Returns a [`map_chars`] mapping that upper cases the first letter of every
+/// word and lower cases the rest. Hyphens start a new word, apostrophes do not.
+fn title_case() -> impl FnMut(char, &mut String) {
+    let mut in_word = false;
+
+    move |c, out| {
+        if c.is_alphanumeric() {
+            if in_word {
+                out.extend(c.to_lowercase());
+            } else {
+                out.extend(c.to_uppercase());
+            }
+
+            in_word = true;
+        } else {
+            out.push(c);
+            in_word = in_word && c == '\'';
+        }
+    }
+}
+
+/// Returns the copy of `text` that keys are matched against in `language`,
+/// along with the offset in `text` each offset in the copy comes from. Unless
+/// matching is case-sensitive, the copy is case-folded like [`fold_case`], but
+/// one char at a time. With `fold_diacritics`, combining marks are left out
+/// after decomposing every char. Every run of whitespace becomes a single
+/// space, so a key with a space in it matches the words it separates however
+/// they are spaced or broken over lines. All of this can change length, so
+/// every offset maps to the char whose folding starts there, or to `None`
+/// inside a char or its folding. Chars left out entirely have no offset.
+fn fold_for_matching(language: &Language, text: &str) -> (String, Vec<Option<usize>>) {
+    fold_with_case(language, text, language.match_case_insensitive)
+}
+
+/// Folds `text` like [`fold_for_matching`], but only case-folds it if
+/// `case_insensitive` is set.
+fn fold_with_case(
+    language: &Language,
+    text: &str,
+    case_insensitive: bool,
+) -> (String, Vec<Option<usize>>) {
+    let mut folded = String::with_capacity(text.len());
+    let mut origins = Vec::with_capacity(text.len() + 1);
+    let push = |c: char, folded: &mut String| {
+        if !case_insensitive {
+            folded.push(c);
+        } else if language.lower_mode == CapitalizationMode::Upper {
+            folded.extend(c.to_uppercase());
+        } else {
+            folded.extend(c.to_lowercase());
+        }
+    };
+
+    let mut after_whitespace = false;
+
+    for (i, c) in text.char_indices() {
+        let start = folded.len();
+
+        if c.is_whitespace() {
+            if !after_whitespace {
+                folded.push(' ');
+            }
+        } else if language.fold_diacritics {
+            unicode_normalization::char::decompose_canonical(c, |c| {
+                if !unicode_normalization::char::is_combining_mark(c) {
+                    push(c, &mut folded);
+                }
+            });
+        } else {
+            push(c, &mut folded);
+        }
+
+        after_whitespace = c.is_whitespace();
+
+        if folded.len() > start {
+            origins.push(Some(i));
+            origins.resize(folded.len(), None);
+        }
+    }
+
+    origins.push(Some(text.len()));
+
+    (folded, origins)
+}
+
+/// Case-folds `s` the way keys are matched under `mode`: to upper case in
+/// `Upper` mode and to lower case otherwise.
+fn fold_case(mode: &CapitalizationMode, s: &str) -> String {
+    match mode {
+        CapitalizationMode::Upper => s.to_uppercase(),
+        _ => s.to_lowercase(),
+    }
+}
+
+/// Strips leading blank lines and trailing whitespace from `text`, keeping the
+/// indentation of the first line and everything in between intact.
+pub fn trim_blank_lines(text: &str) -> &str {
+    &text[trimmed_range(text)]
+}
+
+/// Returns where the part of `text` that [`trim_blank_lines`] keeps is.
+pub fn trimmed_range(text: &str) -> Range<usize> {
+    let end = text.trim_end().len();
+    let start = text[..end]
+        .find(|c: char| !c.is_whitespace())
+        .map_or(end, |first| text[..first].rfind('\n').map_or(0, |n| n + 1));
+
+    start..end
+}
+
+/// Returns the dictionary entries ordered by descending priority and then by
+/// descending key length, so that phrases are replaced before any of the words
+/// they contain. Entries that tie on both keep their insertion order.
+pub fn sorted_entries(dict: &IndexMap<String, Translation>) -> Vec<(&String, &Translation)> {
+    let mut entries: Vec<_> = dict.iter().collect();
+    entries
+        .sort_by_key(|(word, translation)| std::cmp::Reverse((translation.priority(), word.len())));
+    entries
+}
+
+/// A piece of text as split by [`tokenize`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Token<'a> {
+    /// Where the token starts in the text.
+    pub start: usize,
+    pub text: &'a str,
+    /// Whether the token is a word, rather than the space or punctuation
+    /// between words.
+    pub is_word: bool,
+}
+
+/// Splits `text` on the word boundaries of Unicode Standard Annex #29, so that
+/// contractions like `don't` stay one word, hyphenated compounds are split
+/// into their parts and every CJK ideograph is a word of its own. A token is a
+/// word if it contains a letter or digit.
+pub fn tokenize(text: &str) -> impl Iterator<Item = Token<'_>> {
+    text.split_word_bound_indices().map(|(start, text)| Token {
+        start,
+        text,
+        is_word: text.chars().any(char::is_alphanumeric),
+    })
+}
+
+/// Returns for every byte offset in `text` whether a word boundary lies there.
+/// Matching only looks at the offsets where a char starts.
+fn word_boundaries(text: &str) -> Vec<bool> {
+    let mut boundaries = vec![false; text.len() + 1];
+
+    for token in tokenize(text) {
+        boundaries[token.start] = true;
+    }
+    boundaries[text.len()] = true;
+
+    boundaries
+}
+
+/// Adds the `--rule` entries to the first language of `chain`, replacing any
+/// entries it has for the same keys.
+pub fn add_rules(chain: &mut [Language], rules: &[(String, String)]) {
+    if let Some(language) = chain.first_mut() {
+        for (from, to) in rules {
+            language.dict.insert(from.clone(), to.as_str().into());
+        }
+    }
+}
+
+/// Returns the names of the languages in `config`, each once, in the order
+/// they are defined.
+pub fn language_names(config: &Config) -> Vec<&str> {
+    let mut seen = HashSet::new();
+
+    config
+        .languages
+        .iter()
+        .map(|l| l.name.as_str())
+        .filter(|name| seen.insert(*name))
+        .collect()
+}
+
+/// Parses `contents` as JSON or YAML based on the extension of `path`, falling
+/// back to TOML for any other extension.
+pub fn parse_config(path: &str, contents: &str) -> Result<Config, Box<dyn std::error::Error>> {
+    match Path::new(path).extension().and_then(|ext| ext.to_str()) {
+        Some("json") => Ok(serde_json::from_str(contents)?),
+        Some("yaml" | "yml") => Ok(serde_yaml::from_str(contents)?),
+        _ => Ok(toml::from_str(contents)?),
+    }
+}
+
+/// Merges `other` into `base`. Languages are appended, except when `base`
+/// already defines a language with the same name, in which case the one from
+/// `other` replaces it. Duplicates within `other` itself are kept as is.
+/// Global settings from `other` override those in `base`. Returns the names of
+/// the languages that were replaced.
+pub fn merge_config(base: &mut Config, other: Config) -> Vec<String> {
+    let mut overridden = Vec::new();
+    let base_len = base.languages.len();
+
+    for language in other.languages {
+        match base.languages[..base_len]
+            .iter_mut()
+            .find(|l| l.name == language.name)
+        {
+            Some(existing) => {
+                overridden.push(language.name.clone());
+                *existing = language;
+            }
+            None => base.languages.push(language),
+        }
+    }
+
+    base.global = match (base.global.take(), other.global) {
+        (Some(base), Some(other)) => Some(Global {
+            default_language: other.default_language.or(base.default_language),
+            copy_to_clipboard: other.copy_to_clipboard.or(base.copy_to_clipboard),
+            quit_keywords: other.quit_keywords.or(base.quit_keywords),
+            case_sensitive_quit: other.case_sensitive_quit.or(base.case_sensitive_quit),
+            repl_prompt: other.repl_prompt.or(base.repl_prompt),
+        }),
+        (base, other) => other.or(base),
+    };
+
+    overridden
+}
+
+/// Returns every pair of literal keys in `language` that are the same after case
+/// folding, so matching can never reach the second one. The first key of each
+/// pair is the one that wins. Case-sensitive and guarded entries don't always
+/// match, so they never collide.
+pub fn case_collisions(language: &Language) -> Vec<(&str, &str)> {
+    if !language.match_case_insensitive {
+        return Vec::new();
+    }
+
+    let mut seen: HashMap<String, &str> = HashMap::new();
+    let mut collisions = Vec::new();
+
+    for (key, translation) in sorted_entries(&language.dict) {
+        if key.starts_with(PATTERN_PREFIX) {
+            continue;
+        }
+
+        if let Translation::Entry(entry) = translation
+            && (entry.case_sensitive || entry.before.is_some() || entry.after.is_some())
+        {
+            continue;
+        }
+
+        let folded = fold_case(&language.lower_mode, key);
+        match seen.get(&folded) {
+            Some(used) => collisions.push((*used, key.as_str())),
+            None => {
+                seen.insert(folded, key);
+            }
+        }
+    }
+
+    collisions
+}
+
+/// Why the config files could not be loaded.
+#[derive(Debug)]
+pub enum ConfigError {
+    NotFound(String),
+    Read(String, std::io::Error),
+    Parse(String, Box<dyn std::error::Error>),
+}
+
+impl std::error::Error for ConfigError {}
+
+impl std::fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigError::NotFound(path) => write!(f, "Could not find `{path}`"),
+            ConfigError::Read(path, e) => write!(f, "Could not read `{path}`: {e}"),
+            ConfigError::Parse(path, e) => write!(f, "Could not parse `{path}`: {e}"),
+        }
+    }
+}
+
+/// Reads the config files at `paths` and merges them in order, warning about
+/// overridden languages and colliding keys through `log`.
+pub fn load_config(paths: &[String]) -> Result<Config, ConfigError> {
+    let mut config = Config::default();
+
+    for path in paths {
+        let contents = std::fs::read_to_string(path).map_err(|e| match e.kind() {
+            ErrorKind::NotFound => ConfigError::NotFound(path.clone()),
+            _ => ConfigError::Read(path.clone(), e),
+        })?;
+        let mut other =
+            parse_config(path, &contents).map_err(|e| ConfigError::Parse(path.clone(), e))?;
+        let dir = Path::new(path).parent().unwrap_or(Path::new(""));
+
+        for language in &mut other.languages {
+            load_dict_files(language, dir)?;
+        }
+
+        for name in merge_config(&mut config, other) {
+            warn!("Language `{name}` in `{path}` overrides an earlier definition");
+        }
+    }
+
+    for language in &config.languages {
+        for (used, ignored) in case_collisions(language) {
+            warn!(
+                "Keys `{used}` and `{ignored}` in language `{}` match the same text, only `{used}` is used",
+                language.name
+            );
+        }
+    }
+
+    Ok(config)
+}
+
+/// Describes the problem with the `default-language` of `config` if it names a
+/// language, or one in a chain of languages, that is not defined.
+pub fn check_default_language(config: &Config) -> Option<String> {
+    let default = config.global.as_ref()?.default_language.as_ref()?;
+    let name = default
+        .split(',')
+        .map(str::trim)
+        .find(|name| !config.languages.iter().any(|l| l.name == *name))?;
+
+    let mut issue =
+        format!("`default-language` in `[global]` names `{name}`, which is not a defined language");
+    if let Some(suggestion) = suggest_language(&config.languages, name) {
+        issue.push_str(&format!(", did you mean `{suggestion}`?"));
+    }
+
+    Some(issue)
+}
+
+/// Adds the entries of the `dict_files` of `language`, found relative to
+/// `dir`, to its dictionary, logging every key that is overridden.
+pub fn load_dict_files(language: &mut Language, dir: &Path) -> Result<(), ConfigError> {
+    if language.dict_files.is_empty() {
+        return Ok(());
+    }
+
+    let mut dict = IndexMap::new();
+    let mut sources: HashMap<String, &str> = HashMap::new();
+
+    for file in &language.dict_files {
+        for (key, translation) in read_dict_file(&dir.join(file), language.dict_delimiter)? {
+            if let Some(earlier) = sources.insert(key.clone(), file) {
+                warn!(
+                    "Key `{key}` of language `{}` in `{file}` overrides the one in `{earlier}`",
+                    language.name
+                );
+            }
+
+            dict.insert(key, translation);
+        }
+    }
+
+    for (key, translation) in std::mem::take(&mut language.dict) {
+        if let Some(earlier) = sources.get(&key) {
+            warn!(
+                "Key `{key}` of language `{}` in the config overrides the one in `{earlier}`",
+                language.name
+            );
+        }
+
+        dict.insert(key, translation);
+    }
+
+    language.dict = dict;
+
+    Ok(())
+}
+
+/// Reads the dictionary entries in the CSV file at `path`, whose columns are
+/// separated by `delimiter` or else by what its extension suggests.
+fn read_dict_file(
+    path: &Path,
+    delimiter: Option<char>,
+) -> Result<IndexMap<String, Translation>, ConfigError> {
+    let name = path.display().to_string();
+    let contents = std::fs::read_to_string(path).map_err(|e| ConfigError::Read(name.clone(), e))?;
+
+    let delimiter = match delimiter {
+        Some(delimiter) => u8::try_from(delimiter)
+            .ok()
+            .filter(u8::is_ascii)
+            .ok_or_else(|| {
+                ConfigError::Parse(name.clone(), "the delimiter must be ASCII".into())
+            })?,
+        None if path.extension().is_some_and(|ext| ext == "tsv") => b'\t',
+        None => b',',
+    };
+
+    let mut dict = IndexMap::new();
+    let mut reader = csv::ReaderBuilder::new()
+        .has_headers(false)
+        .flexible(true)
+        .delimiter(delimiter)
+        .from_reader(contents.as_bytes());
+
+    for record in reader.records() {
+        let record = record.map_err(|e| ConfigError::Parse(name.clone(), e.into()))?;
+        let mut fields = record.iter();
+        let Some(key) = fields.next().filter(|key| !key.is_empty()) else {
+            continue;
+        };
+        let mut translations: Vec<String> = fields
+            .filter(|field| !field.is_empty())
+            .map(String::from)
+            .collect();
+
+        let translation = match translations.len() {
+            0 => continue,
+            1 => Translation::Single(translations.remove(0)),
+            _ => Translation::Choice(translations),
+        };
+        dict.insert(key.to_string(), translation);
+    }
+
+    Ok(dict)
+}
+
+/// Returns a description of every problem found in `config`.
+pub fn check_config(config: &Config) -> Vec<String> {
+    let mut issues = Vec::new();
+
+    if let Some(issue) = check_default_language(config) {
+        issues.push(issue);
+    }
+
+    let mut seen = HashSet::new();
+
+    for language in &config.languages {
+        if !seen.insert(&language.name) {
+            issues.push(format!(
+                "Language `{}` is defined more than once",
+                language.name
+            ));
+        }
+
+        if language.dict.is_empty() {
+            issues.push(format!(
+                "Language `{}` has an empty dictionary",
+                language.name
+            ));
+        }
+
+        for key in language.dict.keys() {
+            if let Some(Err(e)) = key
+                .strip_prefix(PATTERN_PREFIX)
+                .map(|pattern| build_pattern(pattern, language.match_case_insensitive))
+            {
+                issues.push(format!(
+                    "Language `{}` has an invalid pattern `{key}`: {e}",
+                    language.name
+                ));
+            }
+        }
+
+        if let Some(reverse_dict) = &language.reverse_dict {
+            if reverse_dict.is_empty() {
+                issues.push(format!(
+                    "Language `{}` has an empty reverse dictionary",
+                    language.name
+                ));
+            }
+
+            // Translating back only makes sense for text translated forward.
+            let translations: HashSet<&str> = language
+                .dict
+                .values()
+                .flat_map(Translation::alternatives)
+                .map(String::as_str)
+                .collect();
+
+            for key in reverse_dict.keys() {
+                if !key.starts_with(PATTERN_PREFIX) && !translations.contains(strip_anchors(key).0)
+                {
+                    issues.push(format!(
+                        "Language `{}` reverses `{key}`, which nothing translates to",
+                        language.name
+                    ));
+                }
+            }
+        }
+    }
+
+    issues
+}
+
+/// Looks up every language in the comma-separated list `names`, in order.
+/// With `reverse` set, the chain is turned around and each language in it
+/// reversed, so it translates back to the source. Fails with the first name
+/// that is not defined.
+pub fn resolve_chain(
+    languages: &[Language],
+    names: &str,
+    reverse: bool,
+) -> Result<Vec<Language>, String> {
+    let mut chain = names
+        .split(',')
+        .map(str::trim)
+        .map(|name| {
+            languages
+                .iter()
+                .find(|l| l.name == name)
+                .cloned()
+                .ok_or_else(|| name.to_string())
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    if reverse {
+        chain = chain.iter().rev().map(reverse_language).collect();
+    }
+
+    Ok(chain)
+}
+
+/// Returns the name of the language in `languages` that is closest to `name`,
+/// if it is close enough to be a typo: at most one edit, counting swapped
+/// neighbouring characters as one, per three characters of `name`.
+pub fn suggest_language<'a>(languages: &'a [Language], name: &str) -> Option<&'a str> {
+    let max_distance = name.chars().count() / 3;
+
+    languages
+        .iter()
+        .map(|l| (strsim::osa_distance(&l.name, name), l.name.as_str()))
+        .filter(|(distance, _)| (1..=max_distance).contains(distance))
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, name)| name)
+}
+
+/// Returns the name of a chain of languages as it is given on the command line.
+pub fn chain_name(chain: &[CompiledLanguage]) -> String {
+    chain
+        .iter()
+        .map(|l| l.name.as_str())
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Coverage numbers for one translation.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct Stats {
+    /// Number of words in the input.
+    pub words: usize,
+    /// Number of substitutions made, a matched phrase counting as one.
+    pub matches: usize,
+    /// Number of different dictionary entries that were used.
+    pub distinct_entries: usize,
+    /// Number of input words that no substitution touched.
+    pub unmatched_words: usize,
+}
+
+pub fn stats(text: &str, language: &Language) -> Stats {
+    let (_, substitutions) = translate_matches(text, language, &mut rand::rng());
+    let text = trim_blank_lines(text);
+
+    let mut stats = Stats {
+        matches: substitutions.len(),
+        distinct_entries: substitutions
+            .iter()
+            .map(|s| &s.key)
+            .collect::<HashSet<_>>()
+            .len(),
+        ..Default::default()
+    };
+
+    for word in tokenize(text).filter(|token| token.is_word) {
+        let start = word.start;
+        let end = start + word.text.len();
+
+        stats.words += 1;
+
+        if !substitutions
+            .iter()
+            .any(|s| s.source.start < end && start < s.source.end)
+        {
+            stats.unmatched_words += 1;
+        }
+    }
+
+    stats
+}
+
+/// Returns every distinct word of `text` that translating it with `language`
+/// leaves as it is, in order of first appearance. Words are compared under the
+/// case folding of the language's capitalization mode. A word has a letter in
+/// it, so numbers and punctuation are never untranslated, and neither are
+/// protected words.
+pub fn untranslated_words(text: &str, language: &CompiledLanguage) -> Vec<String> {
+    let mut seen = HashSet::new();
+
+    language
+        .explain(text)
+        .into_iter()
+        .filter(|(word, explanation)| {
+            word.chars().any(char::is_alphabetic)
+                && matches!(
+                    explanation,
+                    Explanation::NoKey | Explanation::Outranked(_) | Explanation::Guarded(_)
+                )
+        })
+        .filter(|(word, _)| seen.insert(fold_case(&language.lower_mode, word)))
+        .map(|(word, _)| word.to_string())
+        .collect()
+}
+
+/// Returns a copy of `language` that translates back, with its reverse
+/// dictionary if it has one. Otherwise the keys and values of its dictionary
+/// are swapped, every alternative translation mapping back to its key. When
+/// several words share a translation, the first one is kept. Pattern keys are
+/// left out.
+pub fn reverse_language(language: &Language) -> Language {
+    if let Some(reverse_dict) = &language.reverse_dict {
+        return Language {
+            dict: reverse_dict
+                .iter()
+                .map(|(word, translation)| (word.clone(), translation.as_str().into()))
+                .collect(),
+            reverse_dict: None,
+            ..language.clone()
+        };
+    }
+
+    let mut dict: IndexMap<String, Translation> = IndexMap::with_capacity(language.dict.len());
+
+    for (word, translation) in &language.dict {
+        if word.starts_with(PATTERN_PREFIX) {
+            warn!("`{word}` is a pattern and can't be reversed, skipping it");
+            continue;
+        }
+
+        // Anchors stay on the key, so they apply to the translation instead.
+        let (stripped, at_start, at_end) = strip_anchors(word);
+
+        for alternative in translation.alternatives() {
+            let key = format!(
+                "{}{alternative}{}",
+                if at_start { "^" } else { "" },
+                if at_end { "$" } else { "" }
+            );
+
+            if let Some(existing) = dict.get(&key) {
+                warn!(
+                    "`{alternative}` is the translation of both `{existing}` and `{word}`, keeping `{existing}`"
+                );
+                continue;
+            }
+
+            dict.insert(key, stripped.into());
+        }
+    }
+
+    Language {
+        dict,
+        reverse_dict: None,
+        ..language.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        let words = |text| {
+            tokenize(text)
+                .filter(|token| token.is_word)
+                .map(|token| token.text)
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            words("Don't stop, it's fine."),
+            ["Don't", "stop", "it's", "fine"]
+        );
+        assert_eq!(words("a well-known man"), ["a", "well", "known", "man"]);
+        assert_eq!(words("我的猫"), ["我", "的", "猫"]);
+        assert_eq!(words("'quoted' 3.5"), ["quoted", "3.5"]);
+
+        let text = "so-so, ok";
+        let tokens: Vec<Token> = tokenize(text).collect();
+        assert_eq!(tokens.iter().map(|t| t.text).collect::<String>(), text);
+        assert!(
+            tokens
+                .iter()
+                .all(|t| &text[t.start..][..t.text.len()] == t.text)
+        );
+    }
+
+    #[test]
+    fn test_suggest_language() {
+        let languages = ["eng2elvish", "eng2pirate", "de"].map(|name| Language {
+            name: name.to_string(),
+            ..Default::default()
+        });
+
+        assert_eq!(
+            suggest_language(&languages, "eng2elvsh"),
+            Some("eng2elvish")
+        );
+        assert_eq!(
+            suggest_language(&languages, "Eng2Pirate"),
+            Some("eng2pirate")
+        );
+        assert_eq!(suggest_language(&languages, "klingon"), None);
+        assert_eq!(suggest_language(&languages, "fr"), None);
+        assert_eq!(suggest_language(&languages, "eng2elvish"), None);
+    }
+
+    #[test]
+    fn test_translator_builder() {
+        assert_eq!(
+            Translator::builder().seed(1).build().unwrap_err(),
+            BuildError::NoLanguage
+        );
+
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: [
+                ("hello".to_string(), "hola".into()),
+                (
+                    "cat".to_string(),
+                    Translation::Choice(vec!["gato".to_string(), "poes".to_string()]),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let build = || {
+            Translator::builder()
+                .language(language.clone())
+                .seed(7)
+                .build()
+                .unwrap()
+        };
+        let mut translator = build();
+        let mut other = build();
+
+        assert_eq!(translator.language(), &language);
+        assert_eq!(translator.translate("Hello World"), "hola world");
+        assert_eq!(other.translate("Hello World"), "hola world");
+        for _ in 0..10 {
+            assert_eq!(translator.translate("cat"), other.translate("cat"));
+        }
+    }
+
+    #[test]
+    fn test_apply_case() {
+        assert_eq!(apply_case("hello", "HoLa"), "hola");
+        assert_eq!(apply_case("HELLO", "hola"), "HOLA");
+        assert_eq!(apply_case("Hello", "hola"), "Hola");
+        assert_eq!(apply_case("hELLO", "hola"), "Hola");
+        assert_eq!(apply_case("I", "e"), "E");
+        assert_eq!(apply_case("Hello", ""), "");
+    }
+
+    /// Compiles every language of `chain`, limiting each to `max_replacements`.
+    fn compile_chain(
+        chain: Vec<Language>,
+        max_replacements: Option<usize>,
+    ) -> Vec<CompiledLanguage> {
+        chain
+            .into_iter()
+            .map(|language| CompiledLanguage::new(language).with_max_replacements(max_replacements))
+            .collect()
+    }
+
+    fn translate_with_rng(text: &str, language: &Language, rng: &mut dyn Rng) -> String {
+        translate_matches(text, language, rng).0
+    }
+
+    /// Translates `text` like [`translate_with_rng`], also returning the byte
+    /// ranges of the translated string that were substituted from the
+    /// dictionary.
+    fn translate_spans(
+        text: &str,
+        language: &Language,
+        rng: &mut dyn Rng,
+    ) -> (String, Vec<Range<usize>>) {
+        translate_chain_spans(text, &[CompiledLanguage::new(language.clone())], rng)
+    }
+
+    fn dict(entries: &[(&str, &str)]) -> IndexMap<String, Translation> {
+        entries
+            .iter()
+            .map(|&(word, translation)| (word.to_string(), translation.into()))
+            .collect()
+    }
+
+    #[test]
+    fn test_translate() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("hello", "hola"), ("world", "mundo")]),
+            ..Default::default()
+        };
+
+        assert_eq!(translate("hello world", &language), "hola mundo");
+        assert_eq!(translate("Hello WorLd", &language), "hola mundo");
+    }
+
+    fn whole_word_language(lower_mode: CapitalizationMode) -> Language {
+        Language {
+            name: "test".to_string(),
+            lower_mode,
+            dict: dict(&[("cat", "gato")]),
+            whole_word: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_whole_word() {
+        for mode in [
+            CapitalizationMode::Lower,
+            CapitalizationMode::Preserve,
+            CapitalizationMode::Upper,
+        ] {
+            let language = whole_word_language(mode.clone());
+            let expected = |s: &str| match mode {
+                CapitalizationMode::Upper => s.to_uppercase(),
+                _ => s.to_string(),
+            };
+
+            assert_eq!(translate("category", &language), expected("category"));
+            assert_eq!(translate("concatenate", &language), expected("concatenate"));
+            assert_eq!(
+                translate("a cat, a dog", &language),
+                expected("a gato, a dog")
+            );
+            assert_eq!(translate("cat", &language), expected("gato"));
+            assert_eq!(
+                translate("cat and dog", &language),
+                expected("gato and dog")
+            );
+            assert_eq!(
+                translate("dog and cat", &language),
+                expected("dog and gato")
+            );
+        }
+    }
+
+    #[test]
+    fn test_longest_match_first() {
+        for mode in [
+            CapitalizationMode::Lower,
+            CapitalizationMode::Preserve,
+            CapitalizationMode::Upper,
+        ] {
+            let language = Language {
+                name: "test".to_string(),
+                lower_mode: mode.clone(),
+                dict: dict(&[("new", "nieuw"), ("new york", "big apple")]),
+                ..Default::default()
+            };
+            let expected = |s: &str| match mode {
+                CapitalizationMode::Upper => s.to_uppercase(),
+                _ => s.to_string(),
+            };
+
+            assert_eq!(
+                translate("new york is new", &language),
+                expected("big apple is nieuw")
+            );
+        }
+    }
+
+    #[test]
+    fn test_sorted_entries_keeps_insertion_order_on_ties() {
+        let dict = dict(&[("ab", "1"), ("abc", "2"), ("cd", "3")]);
+
+        let keys: Vec<&str> = sorted_entries(&dict)
+            .into_iter()
+            .map(|(k, _)| k.as_str())
+            .collect();
+        assert_eq!(keys, ["abc", "ab", "cd"]);
+    }
+
+    #[test]
+    fn test_no_retranslation() {
+        for mode in [
+            CapitalizationMode::Lower,
+            CapitalizationMode::Preserve,
+            CapitalizationMode::Upper,
+        ] {
+            let language = Language {
+                name: "test".to_string(),
+                lower_mode: mode.clone(),
+                dict: dict(&[("hello", "world"), ("world", "earth")]),
+                ..Default::default()
+            };
+            let expected = |s: &str| match mode {
+                CapitalizationMode::Upper => s.to_uppercase(),
+                _ => s.to_string(),
+            };
+
+            assert_eq!(translate("hello", &language), expected("world"));
+            assert_eq!(translate("hello world", &language), expected("world earth"));
+        }
+    }
+
+    #[test]
+    fn test_reverse_round_trip() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("hello", "hola"), ("world", "mundo")]),
+            ..Default::default()
+        };
+        let reversed = reverse_language(&language);
+
+        let translated = translate("hello world", &language);
+        assert_eq!(translated, "hola mundo");
+        assert_eq!(translate(&translated, &reversed), "hello world");
+    }
+
+    #[test]
+    fn test_reverse_keeps_first_duplicate() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("you're", "ur"), ("your", "ur")]),
+            ..Default::default()
+        };
+        let reversed = reverse_language(&language);
+
+        assert_eq!(reversed.dict.len(), 1);
+        assert_eq!(reversed.dict["ur"], "you're".into());
+    }
+
+    #[test]
+    fn test_reverse_with_reverse_dict() {
+        let mut language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("you're", "ur"), ("your", "ur"), ("you", "u")]),
+            ..Default::default()
+        };
+
+        let inverted = reverse_language(&language);
+        assert_eq!(inverted.dict, dict(&[("ur", "you're"), ("u", "you")]));
+        assert_eq!(translate("ur book", &inverted), "you're book");
+
+        language.reverse_dict = Some(
+            [("ur", "your"), ("u", "you")]
+                .into_iter()
+                .map(|(word, translation)| (word.to_string(), translation.to_string()))
+                .collect(),
+        );
+        let reversed = reverse_language(&language);
+        assert_eq!(reversed.dict, dict(&[("ur", "your"), ("u", "you")]));
+        assert_eq!(reversed.reverse_dict, None);
+        assert_eq!(translate("ur book", &reversed), "your book");
+    }
+
+    #[test]
+    fn test_check_reverse_dict() {
+        let config: Config = toml::from_str(
+            r#"
+            [[language]]
+            name = "test"
+            lower-mode = "lower"
+            dict = { hello = "hola", cat = ["gato", "minino"] }
+            reverse-dict = { hola = "hello", minino = "cat", perro = "dog" }
+
+            [[language]]
+            name = "empty"
+            lower-mode = "lower"
+            dict = { a = "b" }
+            reverse-dict = {}
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            check_config(&config),
+            [
+                "Language `test` reverses `perro`, which nothing translates to",
+                "Language `empty` has an empty reverse dictionary",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_preserves_lines() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("hello", "hola"), ("world", "mundo")]),
+            ..Default::default()
+        };
+
+        assert_eq!(translate("hello\nworld", &language), "hola\nmundo");
+        assert_eq!(
+            translate("\n\n  hello\n\n    world  \n", &language),
+            "  hola\n\n    mundo"
+        );
+    }
+
+    #[test]
+    fn test_json_config() {
+        let toml = parse_config(
+            "tranclator.toml",
+            r#"
+            [global]
+            default-language = "test"
+
+            [[language]]
+            name = "test"
+            lower-mode = "lower"
+            dict = { hello = "hola", world = "mundo" }
+            "#,
+        )
+        .unwrap();
+        let json = parse_config(
+            "tranclator.json",
+            r#"{
+                "global": { "default-language": "test" },
+                "language": [
+                    {
+                        "name": "test",
+                        "lower-mode": "lower",
+                        "dict": { "hello": "hola", "world": "mundo" }
+                    }
+                ]
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(json, toml);
+        assert_eq!(
+            translate("Hello World", &json.languages[0]),
+            translate("Hello World", &toml.languages[0])
+        );
+    }
+
+    #[test]
+    fn test_yaml_config() {
+        let toml = parse_config(
+            "tranclator.toml",
+            r#"
+            [[language]]
+            name = "test"
+            lower-mode = "preserve"
+            dict = { hello = "hola", world = "mundo" }
+            "#,
+        )
+        .unwrap();
+        let yaml = "
+language:
+  - name: test
+    lower-mode: preserve
+    dict:
+      hello: hola
+      world: mundo
+";
+
+        for path in ["tranclator.yaml", "tranclator.yml"] {
+            let yaml = parse_config(path, yaml).unwrap();
+
+            assert_eq!(yaml, toml);
+            assert_eq!(
+                translate("Hello world", &yaml.languages[0]),
+                translate("Hello world", &toml.languages[0])
+            );
+        }
+    }
+
+    #[test]
+    fn test_merge_config_concatenates() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [global]
+            default-language = "first"
+            quit-keywords = ["quit"]
+
+            [[language]]
+            name = "first"
+            lower-mode = "lower"
+            dict = {}
+            "#,
+        )
+        .unwrap();
+        let other: Config = toml::from_str(
+            r#"
+            [global]
+            default-language = "second"
+
+            [[language]]
+            name = "second"
+            lower-mode = "upper"
+            dict = {}
+            "#,
+        )
+        .unwrap();
+
+        assert!(merge_config(&mut config, other).is_empty());
+
+        let names: Vec<&str> = config.languages.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, ["first", "second"]);
+
+        let global = config.global.unwrap();
+        assert_eq!(global.default_language.as_deref(), Some("second"));
+        assert_eq!(global.quit_keywords, Some(vec!["quit".to_string()]));
+    }
+
+    #[test]
+    fn test_merge_config_last_language_wins() {
+        let mut config: Config = toml::from_str(
+            r#"
+            [[language]]
+            name = "test"
+            lower-mode = "lower"
+            dict = { hello = "hola" }
+
+            [[language]]
+            name = "other"
+            lower-mode = "lower"
+            dict = {}
+            "#,
+        )
+        .unwrap();
+        let other: Config = toml::from_str(
+            r#"
+            [[language]]
+            name = "test"
+            lower-mode = "lower"
+            dict = { hello = "bonjour" }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(merge_config(&mut config, other), ["test"]);
+        assert_eq!(config.languages.len(), 2);
+        assert_eq!(translate("hello", &config.languages[0]), "bonjour");
+    }
+
+    #[test]
+    fn test_dict_file() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("dicts")).unwrap();
+        std::fs::write(
+            dir.path().join("dicts/words.csv"),
+            "hello,hola\nworld,mundo,tierra\n\"good, bad\",regular\ncat,\n\nthe,el\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("dicts/words.tsv"), "hello\thallo\n").unwrap();
+        let path = dir.path().join("tranclator.toml");
+        std::fs::write(
+            &path,
+            r#"
+            [[language]]
+            name = "spanish"
+            lower-mode = "lower"
+            dict-file = "dicts/words.csv"
+            dict = { the = "la" }
+
+            [[language]]
+            name = "dutch"
+            lower-mode = "lower"
+            dict-file = "dicts/words.tsv"
+            "#,
+        )
+        .unwrap();
+
+        let config = load_config(&[path.to_str().unwrap().to_string()]).unwrap();
+        let spanish = &config.languages[0];
+
+        assert_eq!(
+            spanish.dict.keys().collect::<Vec<_>>(),
+            ["hello", "world", "good, bad", "the"]
+        );
+        assert_eq!(
+            spanish.dict["world"],
+            Translation::Choice(vec!["mundo".to_string(), "tierra".to_string()])
+        );
+        assert_eq!(
+            translate("Hello the cat, good, bad", spanish),
+            "hola la cat, regular"
+        );
+        assert_eq!(translate("hello", &config.languages[1]), "hallo");
+
+        std::fs::write(
+            &path,
+            "[[language]]\nname = \"test\"\nlower-mode = \"lower\"\ndict-file = \"missing.csv\"\n",
+        )
+        .unwrap();
+
+        assert!(matches!(
+            load_config(&[path.to_str().unwrap().to_string()]),
+            Err(ConfigError::Read(..))
+        ));
+    }
+
+    #[test]
+    fn test_dict_files_override_in_order() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("base.csv"),
+            "hello,hola\ncat,gato\nhi,hey\n",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("slang.csv"), "dog,perro\nhello,qué onda\n").unwrap();
+        let path = dir.path().join("tranclator.toml");
+        std::fs::write(
+            &path,
+            r#"
+            [[language]]
+            name = "spanish"
+            lower-mode = "lower"
+            dict-files = ["base.csv", "slang.csv"]
+            dict = { hi = "buenas" }
+            "#,
+        )
+        .unwrap();
+
+        let config = load_config(&[path.to_str().unwrap().to_string()]).unwrap();
+        let spanish = &config.languages[0];
+
+        assert_eq!(
+            spanish.dict.keys().collect::<Vec<_>>(),
+            ["hello", "cat", "hi", "dog"]
+        );
+        assert_eq!(
+            translate("hello cat, hi dog", spanish),
+            "qué onda gato, buenas perro"
+        );
+    }
+
+    #[test]
+    fn test_check_unknown_default_language() {
+        let config: Config = toml::from_str(
+            r#"
+            [global]
+            default-language = "missing"
+
+            [[language]]
+            name = "test"
+            lower-mode = "lower"
+            dict = { a = "b" }
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            check_config(&config),
+            ["`default-language` in `[global]` names `missing`, which is not a defined language"]
+        );
+
+        let config = Config {
+            global: Some(Global {
+                default_language: Some("test, tset".to_string()),
+                ..Default::default()
+            }),
+            ..config
+        };
+
+        assert_eq!(
+            check_config(&config),
+            [
+                "`default-language` in `[global]` names `tset`, which is not a defined language, did you mean `test`?"
+            ]
+        );
+
+        let config = Config {
+            global: Some(Global {
+                default_language: Some("test,test".to_string()),
+                ..Default::default()
+            }),
+            ..config
+        };
+
+        assert!(check_config(&config).is_empty());
+    }
+
+    #[test]
+    fn test_check_duplicate_languages() {
+        let mut config = Config::default();
+        let other: Config = toml::from_str(
+            r#"
+            [[language]]
+            name = "test"
+            lower-mode = "lower"
+            dict = { a = "b" }
+
+            [[language]]
+            name = "test"
+            lower-mode = "upper"
+            dict = { a = "b" }
+            "#,
+        )
+        .unwrap();
+        merge_config(&mut config, other);
+
+        assert_eq!(
+            check_config(&config),
+            ["Language `test` is defined more than once"]
+        );
+    }
+
+    #[test]
+    fn test_check_empty_dictionary() {
+        let config: Config = toml::from_str(
+            r#"
+            [[language]]
+            name = "test"
+            lower-mode = "lower"
+            dict = {}
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            check_config(&config),
+            ["Language `test` has an empty dictionary"]
+        );
+    }
+
+    #[test]
+    fn test_phrases_match_any_whitespace() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Preserve,
+            dict: dict(&[("new york", "nueva york"), ("big  apple", "gran manzana")]),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            translate("New  York and  more", &language),
+            "Nueva york and  more"
+        );
+        assert_eq!(
+            translate("from new\nyork, the big\t\napple", &language),
+            "from nueva york, the gran manzana"
+        );
+        assert_eq!(translate("newyork", &language), "newyork");
+        assert_eq!(
+            translate_matches("new \r\n york", &language, &mut rand::rng()).1[0].source,
+            0..11
+        );
+    }
+
+    #[test]
+    fn test_context_guards() {
+        let config: Config = toml::from_str(
+            r#"
+            [[language]]
+            name = "test"
+            lower-mode = "lower"
+
+            [language.dict]
+            bank = { translation = "orilla", before = "river", otherwise = { translation = "banco de peces", after = "of", otherwise = "banco" } }
+            river = "rio"
+            fish = "pez"
+            left = { translation = "izquierda", after = "bank" }
+            "#,
+        )
+        .unwrap();
+        let language = &config.languages[0];
+
+        assert_eq!(translate("the river bank", language), "the rio orilla");
+        assert_eq!(translate("the River, bank", language), "the rio, orilla");
+        assert_eq!(translate("the bank", language), "the banco");
+        assert_eq!(
+            translate("a bank of fish", language),
+            "a banco de peces of pez"
+        );
+        assert_eq!(translate("left bank", language), "izquierda banco");
+        assert_eq!(translate("left", language), "left");
+        assert_eq!(translate("bank left", language), "banco left");
+    }
+
+    #[test]
+    fn test_case_sensitive_entries() {
+        let config: Config = toml::from_str(
+            r#"
+            [[language]]
+            name = "test"
+            lower-mode = "preserve"
+
+            [language.dict]
+            NASA = { translation = "la NASA", case-sensitive = true }
+            US = { translation = "EEUU", case-sensitive = true, priority = 1 }
+            us = "nosotros"
+            space = "espacio"
+            "#,
+        )
+        .unwrap();
+        let language = &config.languages[0];
+
+        assert_eq!(translate("NASA in space", language), "LA NASA in espacio");
+        assert_eq!(translate("nasa in SPACE", language), "nasa in ESPACIO");
+        assert_eq!(
+            translate("the US and us", language),
+            "the EEUU and nosotros"
+        );
+    }
+
+    #[test]
+    fn test_match_modes() {
+        let language = |match_mode, whole_word| Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("cat", "gato"), ("cats", "gatos"), ("dog", "perro")]),
+            whole_word,
+            match_mode,
+            ..Default::default()
+        };
+        let text = "cat cats concat catsup dogs";
+
+        for (match_mode, whole_word, expected) in [
+            (None, false, "gato gatos congato gatosup perros"),
+            (
+                Some(MatchMode::Substring),
+                true,
+                "gato gatos congato gatosup perros",
+            ),
+            (None, true, "gato gatos concat catsup dogs"),
+            (
+                Some(MatchMode::Whole),
+                false,
+                "gato gatos concat catsup dogs",
+            ),
+            (
+                Some(MatchMode::Prefix),
+                true,
+                "gato gatos concat gatosup perros",
+            ),
+        ] {
+            assert_eq!(
+                translate(text, &language(match_mode, whole_word)),
+                expected,
+                "{match_mode:?}"
+            );
+        }
+
+        let config: Config = toml::from_str(
+            "[[language]]\nname = \"test\"\nlower-mode = \"lower\"\nmatch-mode = \"prefix\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.languages[0].match_mode(), MatchMode::Prefix);
+    }
+
+    #[test]
+    fn test_whole_word_on_unicode_word_boundaries() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("don", "donald"), ("well", "good"), ("猫", "cat")]),
+            whole_word: true,
+            ..Default::default()
+        };
+
+        assert_eq!(translate("don't go, don", &language), "don't go, donald");
+        assert_eq!(
+            translate("a well-known well", &language),
+            "a good-known good"
+        );
+        assert_eq!(translate("我的猫很好", &language), "我的cat很好");
+    }
+
+    #[test]
+    fn test_protected_words() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("hello", "hola"), ("hello rust", "x"), ("rust", "oxido")]),
+            protect: vec!["Rust".to_string()],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            translate("Hello Rust, rusty RUST", &language),
+            "hola Rust, oxidoy RUST"
+        );
+
+        let language = Language {
+            lower_mode: CapitalizationMode::Title,
+            dict: dict(&[("phone", "telephone")]),
+            protect: vec!["iPhone".to_string()],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            translate("my iPhone phone", &language),
+            "My iPhone Telephone"
+        );
+    }
+
+    #[test]
+    fn test_inline_escapes() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Upper,
+            dict: dict(&[("hello", "hola"), ("world", "mundo")]),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            translate("say {{hello}} to the {{big}} world", &language),
+            "SAY hello TO THE big MUNDO"
+        );
+        assert_eq!(translate("{{hello world", &language), "{{HOLA MUNDO");
+        assert_eq!(translate("hello{{}}world", &language), "HOLAMUNDO");
+
+        let language = Language {
+            lower_mode: CapitalizationMode::Sentence,
+            dict: dict(&[("is", "was")]),
+            ..Default::default()
+        };
+
+        assert_eq!(translate("{{rust}} is fun", &language), "rust was fun");
+    }
+
+    #[test]
+    fn test_passthrough_prefix() {
+        let language = Language {
+            lower_mode: CapitalizationMode::Sentence,
+            dict: dict(&[("hello", "hola"), ("world", "mundo")]),
+            passthrough_prefix: Some("#".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            translate(
+                "# Hello world\nhello world\n  #hello {{world}}\nhello # world\r\n# world",
+                &language
+            ),
+            "# Hello world\nhola mundo\n  #hello {{world}}\nhola # mundo\r\n# world"
+        );
+
+        let language = Language {
+            passthrough_prefix: Some(String::new()),
+            ..language
+        };
+
+        assert_eq!(translate("hello world", &language), "Hola mundo");
+    }
+
+    #[test]
+    fn test_protect_placeholders() {
+        let language = Language {
+            lower_mode: CapitalizationMode::Preserve,
+            dict: dict(&[("name", "naam"), ("hello", "hallo"), ("s", "z")]),
+            protect_placeholders: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            translate("Hello {name}, name: %s{user_name}%d", &language),
+            "Hallo {name}, naam: %s{user_name}%d"
+        );
+        assert_eq!(translate("{{name}} {}name", &language), "{{name}} {}naam");
+        assert_eq!(
+            translate("hello {{ user.name }}", &language),
+            "hallo {{ user.name }}"
+        );
+
+        let language = Language {
+            protect_placeholders: false,
+            ..language
+        };
+
+        assert_eq!(translate("Hello {name} %s", &language), "Hallo {naam} %z");
+        assert_eq!(translate("hello {{name}}", &language), "hallo name");
+    }
+
+    #[test]
+    fn test_output_prefix_and_suffix() {
+        let language = Language {
+            lower_mode: CapitalizationMode::Title,
+            dict: dict(&[("hello", "hola"), ("world", "mundo")]),
+            output_prefix: Some("<< hello ".to_string()),
+            output_suffix: Some(" world >>".to_string()),
+            ..Default::default()
+        };
+        let chain = [CompiledLanguage::new(language.clone())];
+
+        let (translated, spans) =
+            translate_chain_spans("hello big\nworld", &chain, &mut rand::rng());
+        assert_eq!(translated, "<< hello Hola Big\nMundo world >>");
+        assert_eq!(spans, [9..13, 18..23]);
+        assert_eq!(translate(" \n", &language), "");
+
+        let language = Language {
+            output_prefix: None,
+            ..language
+        };
+
+        assert_eq!(translate("hello", &language), "Hola world >>");
+    }
+
+    #[test]
+    fn test_digits() {
+        let language = Language {
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("room", "kamer"), ("re:#\\d+", "number")]),
+            digits: Some(["٠", "١", "٢", "٣", "٤", "٥", "٦", "٧", "٨", "٩"].map(String::from)),
+            ..Default::default()
+        };
+
+        assert_eq!(translate("Room 404", &language), "kamer ٤٠٤");
+        assert_eq!(
+            translate("room 12, #37 and {{42}}", &language),
+            "kamer ١٢, number and 42"
+        );
+        assert_eq!(translate("room ٤", &language), "kamer ٤");
+
+        let config: Config = toml::from_str(
+            r#"
+            [[language]]
+            name = "roman"
+            lower-mode = "lower"
+            dict = {}
+            digits = ["", "i", "ii", "iii", "iv", "v", "vi", "vii", "viii", "ix"]
+            "#,
+        )
+        .unwrap();
+        assert_eq!(translate("act 3", &config.languages[0]), "act iii");
+    }
+
+    #[test]
+    fn test_fold_diacritics() {
+        let language = Language {
+            lower_mode: CapitalizationMode::Preserve,
+            dict: dict(&[("cafe", "koffiehuis"), ("creme brulee", "toetje")]),
+            whole_word: true,
+            fold_diacritics: true,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            translate("Café, cafe\u{301} and CRÈME BRÛLÉE at the café.", &language),
+            "Koffiehuis, koffiehuis and TOETJE at the koffiehuis."
+        );
+        assert_eq!(translate("naïve cafés", &language), "naïve cafés");
+
+        let language = Language {
+            dict: dict(&[("café", "koffiehuis"), ("x", "y")]),
+            whole_word: false,
+            match_case_insensitive: false,
+            ..language
+        };
+
+        assert_eq!(
+            translate("cafe Café e\u{301}x", &language),
+            "koffiehuis Café e\u{301}y"
+        );
+
+        let language = Language {
+            fold_diacritics: false,
+            ..language
+        };
+
+        assert_eq!(translate("cafe café", &language), "cafe koffiehuis");
+    }
+
+    #[test]
+    fn test_word_counts_with_contractions_and_compounds() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("don't", "do not"), ("known", "famous")]),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            untranslated_words(
+                "I don't know a well-known don",
+                &CompiledLanguage::new(language.clone())
+            ),
+            ["I", "know", "a", "well", "don"]
+        );
+        assert_eq!(
+            stats("I don't know a well-known don", &language),
+            Stats {
+                words: 7,
+                matches: 2,
+                distinct_entries: 2,
+                unmatched_words: 5,
+            }
+        );
+    }
+
+    #[test]
+    fn test_untranslated_words() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("Hello", "hola"), ("you're", "ur")]),
+            ..Default::default()
+        };
+        let compiled = CompiledLanguage::new(language.clone());
+
+        assert_eq!(
+            untranslated_words("hello, you're a 'good' friend. A friend!", &compiled),
+            ["a", "good", "friend"]
+        );
+        assert!(untranslated_words("HELLO you're", &compiled).is_empty());
+
+        let upper = Language {
+            lower_mode: CapitalizationMode::Upper,
+            ..language
+        };
+        assert_eq!(
+            untranslated_words("hello World", &CompiledLanguage::new(upper)),
+            ["World"]
+        );
+
+        let phrases = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[
+                ("hello", "hola"),
+                ("new york", "nueva york"),
+                ("re:\\d+x", "n"),
+            ]),
+            protect: vec!["Sietse".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            untranslated_words(
+                "hello New  York 3x Sietse cat",
+                &CompiledLanguage::new(phrases)
+            ),
+            ["cat"]
+        );
+    }
+
+    #[test]
+    fn test_explain_without_replacements() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("hello", "hola")]),
+            ..Default::default()
+        };
+        let compiled = CompiledLanguage::new(language).with_max_replacements(Some(0));
+
+        assert_eq!(
+            compiled.explain("hello cat"),
+            [
+                ("hello", Explanation::Outranked("hello".to_string())),
+                ("cat", Explanation::NoKey),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_explain_guarded() {
+        let config: Config = toml::from_str(
+            r#"
+            [[language]]
+            name = "test"
+            lower-mode = "lower"
+
+            [language.dict]
+            bank = { translation = "orilla", before = "river" }
+            "#,
+        )
+        .unwrap();
+        let compiled = CompiledLanguage::new(config.languages[0].clone());
+
+        assert_eq!(
+            compiled.explain("river bank, money bank"),
+            [
+                ("river", Explanation::NoKey),
+                ("bank", Explanation::Matched("bank".to_string())),
+                ("money", Explanation::NoKey),
+                ("bank", Explanation::Guarded("bank".to_string())),
+            ]
+        );
+        assert_eq!(
+            untranslated_words("money bank", &compiled),
+            ["money", "bank"]
+        );
+    }
+
+    #[test]
+    fn test_stats() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[
+                ("new york", "big apple"),
+                ("new", "nieuw"),
+                ("city", "stad"),
+            ]),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            stats("New York is a new city, new york!", &language),
+            Stats {
+                words: 8,
+                matches: 4,
+                distinct_entries: 3,
+                unmatched_words: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn test_translation_choices() {
+        let config: Config = toml::from_str(
+            r#"
+            [[language]]
+            name = "test"
+            lower-mode = "lower"
+            dict = { hello = ["hola", "buenas", "saludos"], world = "mundo" }
+            "#,
+        )
+        .unwrap();
+        let language = &config.languages[0];
+        assert_eq!(
+            language.dict["hello"],
+            Translation::Choice(vec![
+                "hola".to_string(),
+                "buenas".to_string(),
+                "saludos".to_string()
+            ])
+        );
+
+        let text = "hello world hello world hello world hello";
+        let translated = translate_with_rng(text, language, &mut StdRng::seed_from_u64(42));
+        assert_eq!(
+            translated,
+            translate_with_rng(text, language, &mut StdRng::seed_from_u64(42))
+        );
+
+        for word in translated.split(' ') {
+            assert!(["hola", "buenas", "saludos", "mundo"].contains(&word));
+        }
+    }
+
+    #[test]
+    fn test_reverse_choices() {
+        let language = Language {
+            dict: [(
+                "hello".to_string(),
+                Translation::Choice(vec!["hola".to_string(), "buenas".to_string()]),
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let reversed = reverse_language(&language);
+
+        assert_eq!(
+            reversed.dict,
+            dict(&[("hola", "hello"), ("buenas", "hello")])
+        );
+    }
+
+    #[test]
+    fn test_title_mode() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Title,
+            dict: dict(&[("hello", "hola"), ("world", "MUNDO"), ("known", "sabido")]),
+            ..Default::default()
+        };
+
+        assert_eq!(translate("hello world", &language), "Hola Mundo");
+        assert_eq!(translate("HELLO wORLD", &language), "Hola Mundo");
+        assert_eq!(
+            translate("well-known, don't 'tis", &language),
+            "Well-Sabido, Don't 'Tis"
+        );
+
+        let (translated, spans) = translate_spans("hello world", &language, &mut rand::rng());
+        assert_eq!(&translated[spans[1].clone()], "Mundo");
+    }
+
+    #[test]
+    fn test_sentence_mode() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Sentence,
+            dict: dict(&[("hello", "hola"), ("world", "MUNDO")]),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            translate("HELLO world. hello!  world?\nhello", &language),
+            "Hola mundo. Hola!  Mundo?\nHola"
+        );
+        assert_eq!(
+            translate("wait... what?! hello   ", &language),
+            "Wait... What?! Hola"
+        );
+        assert_eq!(translate("pi is 3.14 world", &language), "Pi is 3.14 mundo");
+        // Abbreviations are not recognised, so they end a sentence.
+        assert_eq!(
+            translate("hello, e.g. world", &language),
+            "Hola, e.g. Mundo"
+        );
+    }
+
+    #[test]
+    fn test_preserve_shouted_word() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Preserve,
+            dict: dict(&[("hello", "hola"), ("world", "mundo"), ("f", "ph")]),
+            ..Default::default()
+        };
+
+        assert_eq!(translate("HELLO world", &language), "HOLA mundo");
+        assert_eq!(translate("Hello WORLD", &language), "Hola MUNDO");
+        assert_eq!(translate("my Friend", &language), "my Phriend");
+        assert_eq!(translate("my FRIEND", &language), "my PHRIEND");
+        assert_eq!(translate("F", &language), "Ph");
+    }
+
+    #[test]
+    fn test_preserve_multibyte_case_folding() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Preserve,
+            dict: dict(&[("hello", "hola"), ("café", "koffie")]),
+            ..Default::default()
+        };
+
+        // `İ` is two bytes, but lower cases to the three byte `i̇`.
+        assert_eq!(translate("İİ hello", &language), "İİ hola");
+        assert_eq!(translate("İstanbul Café", &language), "İstanbul Koffie");
+        assert_eq!(translate("ÀÉÎ CAFÉ hello", &language), "ÀÉÎ KOFFIE hola");
+    }
+
+    #[test]
+    fn test_mixed_case_keys_after_multibyte_chars() {
+        for mode in [CapitalizationMode::Preserve, CapitalizationMode::Lower] {
+            let language = Language {
+                name: "test".to_string(),
+                lower_mode: mode,
+                dict: dict(&[("HeLLo", "hola"), ("wörld", "mundo")]),
+                ..Default::default()
+            };
+            let text = "Ⱥß İ… hElLo WÖRLD";
+            let (_, substitutions) = translate_matches(text, &language, &mut rand::rng());
+
+            let sources: Vec<&str> = substitutions
+                .iter()
+                .map(|s| &text[s.source.clone()])
+                .collect();
+            assert_eq!(sources, ["hElLo", "WÖRLD"]);
+        }
+
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Preserve,
+            dict: dict(&[("HeLLo", "hola"), ("wörld", "mundo")]),
+            ..Default::default()
+        };
+        assert_eq!(
+            translate("Ⱥß İ… Hello WÖRLD", &language),
+            "Ⱥß İ… Hola MUNDO"
+        );
+    }
+
+    #[test]
+    fn test_pattern_capture_groups() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Preserve,
+            dict: dict(&[(r"re:(\w+)ies\b", "${1}y-ies")]),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            translate("ponies and Cookies", &language),
+            "pony-ies and Cooky-ies"
+        );
+    }
+
+    #[test]
+    fn test_mixed_literal_and_pattern_keys() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("cat", "kat"), (r"re:(\w+)s\b", "${1}en"), ("dog", "hond")]),
+            whole_word: true,
+            ..Default::default()
+        };
+
+        // The literal `cat` doesn't match inside `cats`, so the pattern does.
+        assert_eq!(
+            translate("Cats, a cat and a dog", &language),
+            "caten, a kat and a hond"
+        );
+        assert_eq!(
+            translate_matches("dogs", &language, &mut rand::rng()).1[0].key,
+            r"re:(\w+)s\b"
+        );
+    }
+
+    #[test]
+    fn test_check_reports_invalid_patterns() {
+        let config = Config {
+            languages: vec![Language {
+                name: "test".to_string(),
+                dict: dict(&[("re:(", "x"), ("re:ok", "y")]),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let issues = check_config(&config);
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].starts_with("Language `test` has an invalid pattern `re:(`"));
+    }
+
+    #[test]
+    fn test_case_insensitive_matching_without_preserve() {
+        let language = |match_case_insensitive| Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("Hello", "hola")]),
+            match_case_insensitive,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            translate("Hello HELLO hello", &language(true)),
+            "hola hola hola"
+        );
+        assert_eq!(
+            translate("Hello HELLO hello", &language(false)),
+            "hola hello hello"
+        );
+    }
+
+    #[test]
+    fn test_case_sensitive_matching() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Preserve,
+            dict: dict(&[("hello", "hola"), ("re:w(o)rld", "m${1}ndo")]),
+            match_case_insensitive: false,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            translate("hello Hello world WORLD", &language),
+            "hola Hello mondo WORLD"
+        );
+    }
+
+    #[test]
+    fn test_match_case_insensitive_defaults_to_true() {
+        let config: Config = toml::from_str(
+            r#"
+            [[language]]
+            name = "test"
+            lower-mode = "lower"
+            dict = { hello = "hola" }
+            "#,
+        )
+        .unwrap();
+
+        assert!(config.languages[0].match_case_insensitive);
+    }
+
+    #[test]
+    fn test_anchored_keys() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("^cat", "kat"), ("dog$", "hond")]),
+            ..Default::default()
+        };
+
+        assert_eq!(translate("scat catalog", &language), "scat katalog");
+        assert_eq!(translate("doghouse hotdog", &language), "doghouse hothond");
+        assert_eq!(translate("cat dog", &language), "kat hond");
+    }
+
+    #[test]
+    fn test_reverse_keeps_anchors_on_keys() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("^un", "non"), ("ness$", "ity")]),
+            ..Default::default()
+        };
+        let reversed = reverse_language(&language);
+
+        assert_eq!(reversed.dict, dict(&[("^non", "un"), ("ity$", "ness")]));
+        assert_eq!(translate("nonclarity", &reversed), "unclarness");
+    }
+
+    fn chain_languages() -> Vec<Language> {
+        vec![
+            Language {
+                name: "eng2spa".to_string(),
+                lower_mode: CapitalizationMode::Lower,
+                dict: dict(&[("hello", "hola")]),
+                ..Default::default()
+            },
+            Language {
+                name: "spa2haw".to_string(),
+                lower_mode: CapitalizationMode::Upper,
+                dict: dict(&[("hola", "aloha")]),
+                ..Default::default()
+            },
+        ]
+    }
+
+    #[test]
+    fn test_translate_chain() {
+        let chain = compile_chain(chain_languages(), None);
+
+        assert_eq!(
+            translate_chain("Hello there", &chain, &mut rand::rng()),
+            "ALOHA THERE"
+        );
+
+        let (translated, spans) = translate_chain_spans("Hello there", &chain, &mut rand::rng());
+        assert_eq!(&translated[spans[0].clone()], "ALOHA");
+    }
+
+    #[test]
+    fn test_translate_fragments() {
+        let chain = compile_chain(chain_languages(), None);
+        let mut text = "hello `hello` hello".to_string();
+        let mut fragments = vec![0..6, 13..19];
+
+        let made;
+        (text, fragments, made) =
+            chain[0].translate_fragments(&text, &fragments, 0, &mut rand::rng());
+        assert_eq!(text, "hola `hello` hola");
+        assert_eq!(fragments, [0..5, 12..17]);
+        assert_eq!(made, 2);
+
+        (text, fragments, _) =
+            chain[1].translate_fragments(&text, &fragments, made, &mut rand::rng());
+        assert_eq!(text, "ALOHA `hello` ALOHA");
+        assert_eq!(fragments, [0..6, 13..19]);
+    }
+
+    #[test]
+    fn test_translate_chain_max_replacements() {
+        let chain = compile_chain(chain_languages(), Some(3));
+
+        assert_eq!(
+            translate_chain_counted("hello hello", &chain, &mut rand::rng()),
+            ("ALOHA HOLA".to_string(), 3)
+        );
+        assert_eq!(
+            translate_chain_fragments(
+                "hello `hello` hello",
+                &[0..6, 13..19],
+                &chain,
+                &mut rand::rng()
+            ),
+            "ALOHA `hello` HOLA"
+        );
+
+        let chain = compile_chain(chain_languages(), Some(1));
+        assert_eq!(
+            translate_chain("hello hello", &chain, &mut rand::rng()),
+            "HOLA HELLO"
+        );
+        assert!(
+            translate_chain_matches("hello", &chain, &mut rand::rng())
+                .1
+                .is_empty()
+        );
+    }
+
+    #[test]
+    fn test_resolve_chain() {
+        let languages = chain_languages();
+
+        let chain = compile_chain(
+            resolve_chain(&languages, "eng2spa, spa2haw", false).unwrap(),
+            None,
+        );
+        assert_eq!(chain_name(&chain), "eng2spa,spa2haw");
+
+        let reversed = resolve_chain(&languages, "eng2spa,spa2haw", true).unwrap();
+        assert_eq!(reversed[0].dict, dict(&[("aloha", "hola")]));
+        assert_eq!(
+            translate_chain("aloha", &compile_chain(reversed, None), &mut rand::rng()),
+            "hello"
+        );
+
+        assert_eq!(
+            resolve_chain(&languages, "eng2spa,elvish", false),
+            Err("elvish".to_string())
+        );
+    }
+
+    #[test]
+    fn test_priority_overrides_key_length() {
+        let config: Config = toml::from_str(
+            r#"
+            [[language]]
+            name = "test"
+            lower-mode = "lower"
+
+            [language.dict]
+            "hello world" = "hola mundo"
+            "hello" = { translation = "hi", priority = 1 }
+            "world" = { translation = ["earth"], priority = -1 }
+            "#,
+        )
+        .unwrap();
+        let language = &config.languages[0];
+
+        assert_eq!(
+            language.dict["hello"],
+            Translation::Entry(Entry {
+                translation: vec!["hi".to_string()],
+                priority: 1,
+                note: None,
+                before: None,
+                after: None,
+                otherwise: None,
+                case_sensitive: false,
+            })
+        );
+        assert_eq!(translate("hello world", language), "hi earth");
+        assert_eq!(translate("say world", language), "say earth");
+
+        let keys: Vec<&str> = sorted_entries(&language.dict)
+            .into_iter()
+            .map(|(key, _)| key.as_str())
+            .collect();
+        assert_eq!(keys, ["hello", "hello world", "world"]);
+    }
+
+    #[test]
+    fn test_case_collisions() {
+        let mut language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[
+                ("Cat", "kat"),
+                ("dog", "hond"),
+                ("cat", "poes"),
+                ("CAT", "mies"),
+            ]),
+            ..Default::default()
+        };
+
+        assert_eq!(case_collisions(&language), [("Cat", "cat"), ("Cat", "CAT")]);
+
+        language.match_case_insensitive = false;
+        assert!(case_collisions(&language).is_empty());
+
+        let config: Config = toml::from_str(
+            r#"
+            [[language]]
+            name = "test"
+            lower-mode = "lower"
+
+            [language.dict]
+            US = { translation = "VS", case-sensitive = true }
+            us = "ons"
+            bank = { translation = "oever", before = "river" }
+            Bank = "bank"
+            "#,
+        )
+        .unwrap();
+        assert!(case_collisions(&config.languages[0]).is_empty());
+    }
+
+    #[test]
+    fn test_language_names() {
+        let config: Config = toml::from_str(
+            r#"
+            [[language]]
+            name = "pirate"
+            lower-mode = "lower"
+            dict = {}
+
+            [[language]]
+            name = "formal"
+            lower-mode = "preserve"
+            dict = {}
+
+            [[language]]
+            name = "pirate"
+            lower-mode = "upper"
+            dict = {}
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(language_names(&config), ["pirate", "formal"]);
+    }
+
+    #[test]
+    fn test_max_replacements() {
+        let language = Language {
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("a", "b"), ("re:c+", "d")]),
+            ..Default::default()
+        };
+        let chain = compile_chain(vec![language], Some(2));
+
+        let (translated, substitutions) =
+            translate_chain_matches("A ccc a a c", &chain, &mut rand::rng());
+        assert_eq!(translated, "b d a a c");
+        assert_eq!(substitutions.len(), 2);
+
+        let chain = compile_chain(chain_languages(), Some(0));
+        assert_eq!(
+            translate_chain("Hello world", &chain, &mut rand::rng()),
+            "HELLO WORLD"
+        );
+    }
+
+    #[test]
+    fn test_count_matches() {
+        let language = CompiledLanguage::new(Language {
+            dict: dict(&[("hello", "hola"), ("hello world", "x"), ("re:\\d+", "n")]),
+            ..Default::default()
+        });
+        let text = "\nhello world, hello 42 and 7 {{hello}} hellos\n";
+
+        assert_eq!(language.count_matches(text), 5);
+        assert_eq!(
+            language.translate_matches(text, &mut rand::rng()).1.len(),
+            5
+        );
+        assert_eq!(language.count_matches(""), 0);
+    }
+
+    #[test]
+    fn test_compiled_language_matches_single_shot() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Preserve,
+            dict: {
+                let mut dict = dict(&[
+                    ("hello world", "hola mundo"),
+                    ("hello", "hola"),
+                    ("^un", "non"),
+                    (r"re:(\d+)%", "$1 percent"),
+                ]);
+                dict.insert(
+                    "cat".to_string(),
+                    Translation::Choice(vec!["gato".to_string(), "poes".to_string()]),
+                );
+                dict
+            },
+            whole_word: true,
+            ..Default::default()
+        };
+        let compiled = CompiledLanguage::new(language.clone());
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut single_shot_rng = StdRng::seed_from_u64(3);
+
+        for text in [
+            "Hello world, hello cat",
+            "HELLO unhello 50%",
+            "cat cat cat cat",
+            "\n\nHello there\n",
+            "",
+            "Hello world, hello cat",
+        ] {
+            assert_eq!(
+                compiled.translate_matches(text, &mut rng),
+                translate_matches(text, &language, &mut single_shot_rng),
+                "{text:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_engine_outputs() {
+        let language = |lower_mode, whole_word| Language {
+            name: "test".to_string(),
+            lower_mode,
+            dict: {
+                let mut dict = dict(&[
+                    ("he", "hy"),
+                    ("hello", "hola"),
+                    ("hell", "infierno"),
+                    ("hello world", "hola mundo"),
+                    ("o", "0"),
+                    ("^un", "non"),
+                    ("ing$", "ung"),
+                    (r"re:(\d+)%", "$1 percent"),
+                    ("ß", "ss"),
+                ]);
+                dict.insert(
+                    "or".to_string(),
+                    Translation::Entry(Entry {
+                        translation: vec!["OR".to_string()],
+                        priority: 1,
+                        note: None,
+                        before: None,
+                        after: None,
+                        otherwise: None,
+                        case_sensitive: false,
+                    }),
+                );
+                dict
+            },
+            whole_word,
+            ..Default::default()
+        };
+        let inputs = [
+            "Hello world, hell is here. HELLO WORLD!",
+            "unhelpful undoing hellos or world 50% SINGING",
+            "Straße shell othello hello-world",
+            "The hero's unhello rings 100% true",
+        ];
+        let mut outputs = Vec::new();
+
+        for mode in [
+            CapitalizationMode::Preserve,
+            CapitalizationMode::Lower,
+            CapitalizationMode::Upper,
+            CapitalizationMode::Title,
+        ] {
+            for whole_word in [false, true] {
+                let language = language(mode.clone(), whole_word);
+                for input in inputs {
+                    outputs.push(translate(input, &language));
+                }
+            }
+        }
+
+        assert_eq!(
+            outputs,
+            [
+                "Hola mundo, infierno is hyre. HOLA MUNDO!",
+                "nonhylpful nond0ung holas or world 50 percent SINGUNG",
+                "StraSse sinfierno 0thola hola-world",
+                "Thy hyr0's nonhola rings 100 percent true",
+                "Hola mundo, infierno is here. HOLA MUNDO!",
+                "unhelpful undoing hellos or world 50 percent SINGING",
+                "Straße shell othello hola-world",
+                "The hero's unhello rings 100 percent true",
+                "hola mundo, infierno is hyre. hola mundo!",
+                "nonhylpful nond0ung holas or world 50 percent singung",
+                "strasse sinfierno 0thola hola-world",
+                "thy hyr0's nonhola rings 100 percent true",
+                "hola mundo, infierno is here. hola mundo!",
+                "unhelpful undoing hellos or world 50 percent singing",
+                "straße shell othello hola-world",
+                "the hero's unhello rings 100 percent true",
+                "HOLA MUNDO, INFIERNO IS HYRE. HOLA MUNDO!",
+                "NONHYLPFUL NOND0UNG HOLAS OR WORLD 50 PERCENT SINGUNG",
+                "STRASSE SINFIERNO 0THOLA HOLA-WORLD",
+                "THY HYR0'S NONHOLA RINGS 100 PERCENT TRUE",
+                "HOLA MUNDO, INFIERNO IS HERE. HOLA MUNDO!",
+                "UNHELPFUL UNDOING HELLOS OR WORLD 50 PERCENT SINGING",
+                "STRASSE SHELL OTHELLO HOLA-WORLD",
+                "THE HERO'S UNHELLO RINGS 100 PERCENT TRUE",
+                "Hola Mundo, Infierno Is Hyre. Hola Mundo!",
+                "Nonhylpful Nond0ung Holas Or World 50 Percent Singung",
+                "Strasse Sinfierno 0thola Hola-World",
+                "Thy Hyr0's Nonhola Rings 100 Percent True",
+                "Hola Mundo, Infierno Is Here. Hola Mundo!",
+                "Unhelpful Undoing Hellos Or World 50 Percent Singing",
+                "Straße Shell Othello Hola-World",
+                "The Hero's Unhello Rings 100 Percent True",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_substring_by_default() {
+        let language = Language {
+            whole_word: false,
+            ..whole_word_language(CapitalizationMode::Lower)
+        };
+
+        assert_eq!(translate("category", &language), "gatoegory");
+    }
+}
@@ -1,227 +1,3929 @@
 use arboard::Clipboard;
-use clap::Parser;
+use clap::{ArgAction, CommandFactory, Parser, Subcommand, ValueEnum};
+use clap_complete::engine::{ArgValueCandidates, CompletionCandidate};
+use clap_complete::{CompleteEnv, Shell};
+use directories::ProjectDirs;
+use encoding_rs::Encoding;
+use globset::{Glob, GlobMatcher};
 use indexmap::map::IndexMap;
-use serde::Deserialize;
-use std::collections::HashSet;
-use std::io::{ErrorKind, Write};
+use log::{LevelFilter, debug, info};
+use pulldown_cmark::{Event, LinkType, Options, Tag};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use regex::Regex;
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+use serde::Serialize;
+use std::collections::{HashSet, VecDeque};
+use std::io::{BufRead, ErrorKind, IsTerminal, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Instant;
+use tranclator::config_writer::ConfigWriter;
+use tranclator::{
+    CapitalizationMode, CompiledLanguage, Config, ConfigError, Explanation, Global, Language,
+    PATTERN_PREFIX, Substitution, Translation, add_rules, chain_name, check_config,
+    check_default_language, language_names, load_config, merge_config, parse_config, resolve_chain,
+    reverse_language, stats, strip_anchors, suggest_language, translate_chain,
+    translate_chain_counted, translate_chain_fragments, translate_chain_matches,
+    translate_chain_spans, trim_blank_lines, trimmed_range, untranslated_words,
+};
+use walkdir::WalkDir;
 
-#[derive(Parser, Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[derive(Parser, Debug, Clone, PartialEq, Eq, Hash)]
 struct Cli {
     #[clap(long, help = "Text to translate", conflicts_with = "repl")]
     text: Option<String>,
+    #[clap(
+        value_name = "FILE",
+        help = "Files to translate, each into a file next to it with the extension `.out`",
+        conflicts_with_all = ["text", "repl", "input_file", "from_clipboard", "watch", "output_file"]
+    )]
+    files: Vec<String>,
+    #[clap(
+        long,
+        value_name = "DIR",
+        help = "Translate every file in a directory tree into --output-dir",
+        conflicts_with_all = ["text", "repl", "input_file", "from_clipboard", "watch", "output_file", "files"]
+    )]
+    recursive: Option<PathBuf>,
+    #[clap(
+        long,
+        value_name = "DIR",
+        help = "Where --recursive writes the translated tree [default: the directory with `.out` appended]",
+        requires = "recursive"
+    )]
+    output_dir: Option<PathBuf>,
+    #[clap(
+        long,
+        value_name = "PATTERN",
+        value_parser = Glob::new,
+        help = "Only translate the files whose path under --recursive matches this glob, like `*.txt`",
+        requires = "recursive"
+    )]
+    glob: Option<Glob>,
     #[clap(long, help = "Run in REPL mode")]
     repl: bool,
-    #[clap(long, default_value = "tranclator.toml", help = "Path to config file")]
-    config_path: String,
-    #[clap(short, long, help = "Language to use")]
+    #[clap(
+        long,
+        value_name = "PATH",
+        num_args = 0..=1,
+        help = "Keep REPL history across sessions, in the cache directory unless a path is given"
+    )]
+    history_file: Option<Option<String>>,
+    #[clap(
+        long,
+        help = "Path to config file, can be given multiple times to merge configs [default: $TRANCLATOR_CONFIG, or tranclator.toml]"
+    )]
+    config_path: Vec<String>,
+    #[clap(
+        short,
+        long,
+        help = "Language to use, or a comma-separated list of languages to translate through in order",
+        add = ArgValueCandidates::new(language_candidates)
+    )]
     language: Option<String>,
+    #[clap(
+        long,
+        value_name = "FROM=TO",
+        value_parser = parse_rule,
+        help = "Extra dictionary entry for the language, overriding the config; can be given multiple times"
+    )]
+    rule: Vec<(String, String)>,
+    #[clap(
+        long,
+        value_enum,
+        help = "Translate with only the --rule entries in this capitalization mode, without reading a config",
+        requires = "rule",
+        conflicts_with_all = ["language", "reverse"]
+    )]
+    lower_mode: Option<CapitalizationMode>,
+    #[clap(
+        long,
+        value_enum,
+        help = "Capitalize the translation in this mode instead of the languages' lower-mode",
+        conflicts_with = "lower_mode"
+    )]
+    capitalization: Option<CapitalizationMode>,
     #[clap(short, long, help = "Do not copy to clipboard")]
     no_clipboard: bool,
+    #[clap(
+        long,
+        value_name = "MS",
+        default_value_t = 100,
+        help = "Milliseconds to wait after copying so clipboard managers can pick up the text"
+    )]
+    clipboard_wait: u64,
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = ClipboardTarget::Clipboard,
+        help = "Which selection receives the translation"
+    )]
+    clipboard_target: ClipboardTarget,
+    #[clap(long, help = "Translate from the language back to the source")]
+    reverse: bool,
+    #[clap(long, help = "List the words that translating leaves untranslated")]
+    report_missing: bool,
+    #[clap(long, help = "Print dictionary coverage statistics")]
+    stats: bool,
+    #[clap(
+        long,
+        help = "Seed for picking between alternative translations, random if not given"
+    )]
+    seed: Option<u64>,
+    #[clap(
+        long,
+        help = "File to translate",
+        conflicts_with_all = ["text", "repl"]
+    )]
+    input_file: Option<String>,
+    #[clap(
+        long,
+        help = "Translate the current clipboard contents",
+        conflicts_with_all = ["text", "repl", "input_file"]
+    )]
+    from_clipboard: bool,
+    #[clap(
+        long,
+        help = "Keep translating whatever is copied to the clipboard",
+        conflicts_with_all = ["text", "repl", "input_file", "from_clipboard", "output_file"]
+    )]
+    watch: bool,
+    #[clap(
+        long,
+        value_name = "MS",
+        default_value_t = 500,
+        help = "Milliseconds between clipboard checks in watch mode"
+    )]
+    watch_interval: u64,
+    #[clap(
+        long,
+        help = "File to write the translation to",
+        conflicts_with = "repl"
+    )]
+    output_file: Option<String>,
+    #[clap(
+        long,
+        value_enum,
+        default_value_t = OutputFormat::Text,
+        help = "How to print one-shot translations",
+        conflicts_with_all = ["repl", "watch"]
+    )]
+    format: OutputFormat,
+    #[clap(
+        long,
+        help = "Translate stdin or --input-file line by line as it is read, without copying to the clipboard",
+        conflicts_with_all = ["text", "repl", "from_clipboard", "watch", "files", "recursive", "format", "report_missing", "stats"]
+    )]
+    stream: bool,
+    #[clap(
+        long,
+        help = "Print every substitution and the translation without copying it to the clipboard",
+        conflicts_with_all = ["repl", "from_clipboard", "watch", "files", "recursive", "stream", "format", "output_file"]
+    )]
+    dry_run: bool,
+    #[clap(
+        long,
+        value_name = "REGEX",
+        value_parser = parse_regex,
+        help = "Only translate the lines that match this regular expression, copying the others as they are",
+        conflicts_with_all = ["repl", "watch", "stream", "format", "dry_run"]
+    )]
+    only_matching: Option<String>,
+    #[clap(
+        short,
+        long,
+        action = ArgAction::Count,
+        help = "Log the config, language and clipboard decisions to stderr, give twice for more detail"
+    )]
+    verbose: u8,
+    #[clap(
+        short,
+        long,
+        help = "Only print translations and errors, leaving out warnings, the REPL banner and other messages",
+        conflicts_with = "verbose"
+    )]
+    quiet: bool,
+    #[clap(
+        long,
+        help = "Keep the blank lines and whitespace around the input instead of trimming them",
+        conflicts_with_all = ["files", "recursive", "stream", "format", "dry_run"]
+    )]
+    no_trim: bool,
+    #[clap(
+        long,
+        value_name = "N",
+        help = "Make at most this many replacements per translation, in all languages of the chain together, leaving the rest untranslated"
+    )]
+    max_replacements: Option<usize>,
+    #[clap(
+        long,
+        help = "Print how long loading the config and translating took to stderr"
+    )]
+    time: bool,
+    #[clap(
+        long,
+        help = "Overwrite --input-file with its translation",
+        requires = "input_file",
+        conflicts_with_all = ["output_file", "stream", "format", "dry_run"]
+    )]
+    in_place: bool,
+    #[clap(
+        long,
+        help = "Keep the original of an --in-place file with `.bak` appended to its name",
+        requires = "in_place"
+    )]
+    backup: bool,
+    #[clap(
+        long,
+        help = "Print only how many dictionary matches the input has, without translating it",
+        conflicts_with_all = ["repl", "watch", "files", "recursive", "stream", "format", "dry_run", "only_matching", "output_file", "in_place"]
+    )]
+    count_only: bool,
+    #[clap(
+        long,
+        help = "Print for every word of the input why it is translated or not, without copying to the clipboard",
+        conflicts_with_all = ["repl", "watch", "files", "recursive", "stream", "format", "dry_run", "only_matching", "output_file", "in_place", "count_only"]
+    )]
+    explain: bool,
+    #[clap(
+        long,
+        help = "Parse the input as JSON and translate only its string values, keeping keys and structure",
+        conflicts_with_all = ["repl", "watch", "files", "recursive", "stream", "format", "dry_run", "only_matching", "count_only", "explain"]
+    )]
+    json_values: bool,
+    #[clap(
+        long,
+        value_name = "PATH",
+        help = "Only translate the JSON strings at or below this dotted path, in which `*` matches any key or index. Can be given several times",
+        requires = "json_values"
+    )]
+    json_path: Vec<String>,
+    #[clap(
+        long,
+        help = "Parse the input as Markdown and translate only its prose, keeping code, HTML and link URLs",
+        conflicts_with_all = ["repl", "watch", "files", "recursive", "stream", "format", "dry_run", "only_matching", "count_only", "explain", "json_values"]
+    )]
+    markdown: bool,
+    #[clap(
+        long,
+        help = "Fail without translating if a word of the input has no dictionary entry, listing those words. Numbers, punctuation and protected words don't count",
+        conflicts_with_all = ["repl", "watch", "files", "recursive", "stream", "only_matching", "json_values", "markdown"]
+    )]
+    strict: bool,
+    #[clap(
+        long,
+        value_name = "ENCODING",
+        value_parser = parse_encoding,
+        help = "Encoding to write the translation in, such as utf8 or latin1 [default: utf8]",
+        conflicts_with_all = ["repl", "watch", "files", "recursive", "stream", "dry_run"]
+    )]
+    output_encoding: Option<&'static Encoding>,
+    #[clap(
+        long,
+        help = "In the REPL, ask for the translation of every unknown word and add it to the config file",
+        requires = "repl",
+        conflicts_with = "reverse"
+    )]
+    learn: bool,
+    #[clap(subcommand)]
+    command: Option<Command>,
 }
 
-#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
-#[serde(rename_all = "kebab-case")]
-struct Config {
-    global: Option<Global>,
-    #[serde(rename = "language", default)]
-    languages: Vec<Language>,
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum ClipboardTarget {
+    Clipboard,
+    Primary,
+    Both,
+}
+
+impl std::fmt::Display for ClipboardTarget {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            ClipboardTarget::Clipboard => "clipboard",
+            ClipboardTarget::Primary => "primary",
+            ClipboardTarget::Both => "both",
+        })
+    }
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq, Hash)]
+enum Command {
+    #[clap(about = "List the languages defined in the config file")]
+    ListLanguages,
+    #[clap(about = "Print the dictionary of the selected language")]
+    ShowDict,
+    #[clap(about = "Write a starter config file to the config path")]
+    Init {
+        #[clap(long, help = "Overwrite the config file if it already exists")]
+        force: bool,
+    },
+    #[clap(about = "Validate the config file without translating anything")]
+    Check,
+    #[clap(
+        about = "Count how often each dictionary key matches in the files under a directory, most used first"
+    )]
+    Report {
+        #[clap(value_name = "DIR")]
+        dir: PathBuf,
+    },
+    #[clap(
+        about = "Check that every dictionary entry translates back to its key, listing the ones that don't"
+    )]
+    VerifyRoundtrip,
+    #[clap(about = "Print a shell completion script to stdout")]
+    Completions {
+        #[clap(value_enum)]
+        shell: Shell,
+    },
+}
+
+/// The ways a run can fail, each mapped to its own process exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Failure {
+    General = 1,
+    ConfigNotFound = 2,
+    ConfigParse = 3,
+    UnknownLanguage = 4,
+    Untranslated = 5,
+}
+
+impl From<Failure> for ExitCode {
+    fn from(failure: Failure) -> Self {
+        ExitCode::from(failure as u8)
+    }
+}
+
+impl From<&ConfigError> for Failure {
+    fn from(e: &ConfigError) -> Self {
+        match e {
+            ConfigError::NotFound(_) => Failure::ConfigNotFound,
+            ConfigError::Read(..) => Failure::General,
+            ConfigError::Parse(..) => Failure::ConfigParse,
+        }
+    }
+}
+
+/// A destination for translated text, such as the system clipboard.
+trait ClipboardSink {
+    fn set_text(&mut self, text: &str) -> Result<(), Box<dyn std::error::Error>>;
+    fn get_text(&mut self) -> Result<String, Box<dyn std::error::Error>>;
+}
+
+/// The system clipboard, writing to the selections picked by `target`.
+struct SystemClipboard {
+    clipboard: Clipboard,
+    target: ClipboardTarget,
 }
 
-#[derive(Deserialize, Debug, Clone, PartialEq, Eq, Default)]
-#[serde(rename_all = "kebab-case")]
-struct Global {
-    default_language: Option<String>,
-    copy_to_clipboard: Option<bool>,
-    quit_keywords: Option<Vec<String>>,
+impl SystemClipboard {
+    fn new(target: ClipboardTarget) -> Result<Self, arboard::Error> {
+        #[cfg(not(all(
+            unix,
+            not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+        )))]
+        let target = if target == ClipboardTarget::Clipboard {
+            target
+        } else {
+            log::warn!(
+                "The primary selection is not supported on this platform, using the clipboard instead"
+            );
+            ClipboardTarget::Clipboard
+        };
+
+        Ok(Self {
+            clipboard: Clipboard::new()?,
+            target,
+        })
+    }
 }
 
-#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
-#[serde(rename_all = "kebab-case")]
-struct Language {
-    name: String,
-    lower_mode: CapitalizationMode,
-    dict: IndexMap<String, String>,
+impl ClipboardSink for SystemClipboard {
+    fn set_text(&mut self, text: &str) -> Result<(), Box<dyn std::error::Error>> {
+        if matches!(
+            self.target,
+            ClipboardTarget::Clipboard | ClipboardTarget::Both
+        ) {
+            self.clipboard.set_text(text)?;
+        }
+
+        #[cfg(all(
+            unix,
+            not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+        ))]
+        if matches!(
+            self.target,
+            ClipboardTarget::Primary | ClipboardTarget::Both
+        ) {
+            use arboard::{LinuxClipboardKind, SetExtLinux};
+
+            self.clipboard
+                .set()
+                .clipboard(LinuxClipboardKind::Primary)
+                .text(text)?;
+        }
+
+        Ok(())
+    }
+
+    fn get_text(&mut self) -> Result<String, Box<dyn std::error::Error>> {
+        #[cfg(all(
+            unix,
+            not(any(target_os = "macos", target_os = "android", target_os = "emscripten"))
+        ))]
+        if self.target == ClipboardTarget::Primary {
+            use arboard::{GetExtLinux, LinuxClipboardKind};
+
+            return Ok(self
+                .clipboard
+                .get()
+                .clipboard(LinuxClipboardKind::Primary)
+                .text()?);
+        }
+
+        Ok(self.clipboard.get_text()?)
+    }
 }
 
-#[derive(Deserialize, Debug, Clone, PartialEq, Eq)]
-#[serde(rename_all = "kebab-case")]
-enum CapitalizationMode {
-    Lower,
-    Preserve,
-    Upper,
+/// Copies `text` to `cb`, then waits for `wait` so that clipboard managers
+/// can take over the contents before the process exits.
+fn copy_and_wait(
+    cb: &mut dyn ClipboardSink,
+    text: &str,
+    wait: std::time::Duration,
+) -> Result<(), Box<dyn std::error::Error>> {
+    cb.set_text(text)?;
+
+    if !wait.is_zero() {
+        std::thread::sleep(wait);
+    }
+
+    Ok(())
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+const STARTER_CONFIG: &str = r#"[global]
+default-language = "example"
+copy-to-clipboard = true
+
+[[language]]
+name = "example"
+lower-mode = "preserve"
+
+[language.dict]
+"hello" = "ahoy"
+"friend" = "matey"
+"#;
+
+fn main() -> Result<ExitCode, Box<dyn std::error::Error>> {
+    CompleteEnv::with_factory(Cli::command).complete();
+
     let args = Cli::parse();
+    init_logging(args.verbose, args.quiet);
+
+    if let Some(Command::Completions { shell }) = args.command {
+        print_completions(shell, &mut std::io::stdout());
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let search = config_search_paths();
+    let config_paths =
+        resolve_config_paths(&args.config_path, std::env::var(CONFIG_ENV).ok(), &search);
+    debug!(
+        "Config search paths: {}",
+        search
+            .iter()
+            .map(|path| format!("`{}`", path.display()))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
 
-    let result = std::fs::read_to_string(&args.config_path);
+    if let Some(Command::Init { force }) = args.command {
+        let path = &config_paths[0];
 
-    let Ok(str) = result else {
-        match result.unwrap_err().kind() {
-            ErrorKind::NotFound => println!("Could not find `{}`", &args.config_path),
-            _ => println!("Could not read config file"),
+        match init_config(path, force) {
+            Ok(()) => inform(args.quiet, format_args!("Wrote starter config to `{path}`")),
+            Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                eprintln!("`{}` already exists, use --force to overwrite it", path);
+                return Ok(Failure::General.into());
+            }
+            Err(e) => return Err(e.into()),
         }
 
-        return Ok(());
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let paths: &[String] = if args.lower_mode.is_some() {
+        &[]
+    } else {
+        &config_paths
     };
 
-    let Ok(config) = toml::from_str::<Config>(&str) else {
-        println!("Could not parse config file");
-        return Ok(());
+    let loading = Instant::now();
+    let config = match load_config(paths) {
+        Ok(config) => {
+            report_time(args.time, "Loading the config", loading);
+            if paths.is_empty() {
+                info!("Not reading a config, translating with --rule only");
+            } else {
+                info!(
+                    "Using config {}",
+                    paths
+                        .iter()
+                        .map(|path| format!("`{path}`"))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            }
+            debug!("Loaded {} languages", config.languages.len());
+
+            config
+        }
+        Err(ConfigError::NotFound(_))
+            if args.config_path.is_empty()
+                && std::env::var_os(CONFIG_ENV).is_none_or(|p| p.is_empty()) =>
+        {
+            eprintln!(
+                "Could not find a config file, looked for {}. Use --config-path or {CONFIG_ENV} to load another file",
+                search
+                    .iter()
+                    .map(|path| format!("`{}`", path.display()))
+                    .collect::<Vec<_>>()
+                    .join(" and ")
+            );
+            return Ok(Failure::ConfigNotFound.into());
+        }
+        Err(e) => {
+            eprintln!("{e}");
+            return Ok(Failure::from(&e).into());
+        }
     };
 
-    let mut cb = if args.no_clipboard {
-        None
+    if let Some(Command::Check) = args.command {
+        let issues = check_config(&config);
+
+        for issue in &issues {
+            eprintln!("{issue}");
+        }
+
+        return Ok(if issues.is_empty() {
+            inform(args.quiet, "No problems found");
+            ExitCode::SUCCESS
+        } else {
+            Failure::General.into()
+        });
+    }
+
+    if let Some(Command::ListLanguages) = args.command {
+        list_languages(&config, &mut std::io::stdout())?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let mut chain = if let Some(lower_mode) = args.lower_mode.clone() {
+        vec![Language {
+            name: "rules".to_string(),
+            lower_mode,
+            ..Default::default()
+        }]
     } else {
-        Some(Clipboard::new()?)
-    };
+        let language = match args.language {
+            Some(language) => {
+                info!("Using language `{language}` from --language");
+                language
+            }
+            None => match config
+                .global
+                .as_ref()
+                .and_then(|g| g.default_language.clone())
+            {
+                Some(language) => {
+                    if let Some(issue) = check_default_language(&config) {
+                        eprintln!("{issue}");
+                        return Ok(Failure::UnknownLanguage.into());
+                    }
 
-    let Some(language) = args
-        .language
-        .or_else(|| config.global.as_ref()?.default_language.clone())
-    else {
-        println!("No language specified");
-        return Ok(());
-    };
+                    info!("Using language `{language}` from `default-language` in the config");
+                    language
+                }
+                None => {
+                    eprintln!("No language specified");
+                    return Ok(Failure::UnknownLanguage.into());
+                }
+            },
+        };
 
-    let Some(language) = config.languages.iter().find(|l| l.name == *language) else {
-        println!("Language {} not found", language);
-        return Ok(());
+        match resolve_chain(&config.languages, &language, args.reverse) {
+            Ok(chain) => chain,
+            Err(name) => {
+                eprintln!("{}", language_not_found(&config.languages, &name));
+                return Ok(Failure::UnknownLanguage.into());
+            }
+        }
     };
+    add_rules(&mut chain, &args.rule);
+    override_capitalization(&mut chain, args.capitalization.as_ref());
+    let chain = compile_chain(chain, args.max_replacements);
+    for language in &chain {
+        info!(
+            "Language `{}` has {} dictionary entries",
+            language.name,
+            language.dict.len()
+        );
+    }
+    let only_matching = args
+        .only_matching
+        .as_deref()
+        .map(|pattern| Regex::new(pattern).expect("checked by parse_regex"));
 
-    if let Some(text) = args.text {
-        let translated = translate(&text, language);
-        println!("{}", translated);
+    // Words are looked up in the language the input is written for.
+    let language = &chain[0];
 
-        if let Some(ref mut cb) = cb {
-            cb.set_text(&translated)?;
-            std::thread::sleep(std::time::Duration::from_millis(100));
+    if let Some(Command::ShowDict) = args.command {
+        for (i, language) in chain.iter().enumerate() {
+            if i > 0 {
+                println!();
+            }
+
+            show_dict(language, &mut std::io::stdout())?;
         }
 
-        return Ok(());
-    } else if args.repl {
-        repl(
-            language,
-            cb,
-            HashSet::from_iter(
-                config
-                    .global
-                    .and_then(|c| c.quit_keywords)
-                    .unwrap_or_default(),
-            ),
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(Command::VerifyRoundtrip) = args.command {
+        let lossless =
+            verify_roundtrip(language, &mut seeded_rng(args.seed), &mut std::io::stdout())?;
+
+        return Ok(if lossless {
+            ExitCode::SUCCESS
+        } else {
+            Failure::General.into()
+        });
+    }
+
+    if let Some(Command::Report { dir }) = &args.command {
+        let failed = report(dir, language, &mut std::io::stdout())?;
+
+        return Ok(if failed {
+            Failure::General.into()
+        } else {
+            ExitCode::SUCCESS
+        });
+    }
+
+    if args.stream {
+        let mut rng = seeded_rng(args.seed);
+        let input: Box<dyn BufRead> = match &args.input_file {
+            Some(path) => match std::fs::File::open(path) {
+                Ok(file) => Box::new(std::io::BufReader::new(file)),
+                Err(e) => {
+                    match e.kind() {
+                        ErrorKind::NotFound => eprintln!("Could not find `{}`", path),
+                        _ => eprintln!("Could not read `{}`", path),
+                    }
+
+                    return Ok(Failure::General.into());
+                }
+            },
+            None => Box::new(std::io::stdin().lock()),
+        };
+
+        let translating = Instant::now();
+        if let Some(path) = &args.output_file {
+            let mut output = std::io::BufWriter::new(std::fs::File::create(path)?);
+            let rest = translate_stream(input, &mut output, &chain, &mut rng)?;
+            output.write_all(with_trailing_newlines(&rest, "").as_bytes())?;
+            output.flush()?;
+        } else {
+            let mut output = std::io::stdout().lock();
+            translate_stream(input, &mut output, &chain, &mut rng)?;
+            writeln!(output)?;
+        }
+        report_time(args.time, "Translating", translating);
+
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(input) = &args.recursive {
+        let output = match &args.output_dir {
+            Some(output) => output.clone(),
+            None => {
+                let mut name = input.as_os_str().to_owned();
+                name.push(".out");
+                PathBuf::from(name)
+            }
+        };
+
+        let translating = Instant::now();
+        let failed = translate_tree(
+            input,
+            &output,
+            args.glob.as_ref().map(Glob::compile_matcher).as_ref(),
+            only_matching.as_ref(),
+            &chain,
+            &mut seeded_rng(args.seed),
+            args.quiet,
         )?;
-        return Ok(());
+        report_time(args.time, "Translating", translating);
+
+        return Ok(if failed {
+            Failure::General.into()
+        } else {
+            ExitCode::SUCCESS
+        });
     }
 
-    Ok(())
-}
+    if !args.files.is_empty() {
+        let mut rng = seeded_rng(args.seed);
+        let mut failed = false;
+        let translating = Instant::now();
 
-fn repl(
-    language: &Language,
-    mut cb: Option<Clipboard>,
-    quit_words: HashSet<String>,
-) -> Result<(), Box<dyn std::error::Error>> {
-    println!("Welcome to {} REPL", language.name);
-    println!(
-        "Type any of {} to exit",
-        quit_words
+        for path in &args.files {
+            let output = batch_output_path(Path::new(path));
+
+            match translate_file(
+                path.as_ref(),
+                &output,
+                only_matching.as_ref(),
+                &chain,
+                &mut rng,
+            ) {
+                Ok(()) => inform(
+                    args.quiet,
+                    format_args!("Translated `{}` to `{}`", path, output.display()),
+                ),
+                Err(e) if e.kind() == ErrorKind::NotFound => {
+                    eprintln!("Could not find `{}`", path);
+                    failed = true;
+                }
+                Err(e) => {
+                    eprintln!("Could not translate `{}`: {}", path, e);
+                    failed = true;
+                }
+            }
+        }
+        report_time(args.time, "Translating", translating);
+
+        return Ok(if failed {
+            Failure::General.into()
+        } else {
+            ExitCode::SUCCESS
+        });
+    }
+
+    let mut cb: Option<Box<dyn ClipboardSink>> = if !args.dry_run
+        && !args.count_only
+        && !args.explain
+        && chain
             .iter()
-            .map(|w| format!("\"{w}\""))
-            .collect::<Vec<String>>()
-            .join(", ")
-    );
+            .all(|language| should_copy(args.no_clipboard, language, config.global.as_ref()))
+    {
+        info!(
+            "Copying translations to the {} clipboard target",
+            args.clipboard_target
+        );
+        Some(Box::new(SystemClipboard::new(args.clipboard_target)?))
+    } else {
+        info!(
+            "Not copying translations to the clipboard because of {}",
+            if args.dry_run {
+                "--dry-run"
+            } else if args.count_only {
+                "--count-only"
+            } else if args.explain {
+                "--explain"
+            } else if args.no_clipboard {
+                "--no-clipboard"
+            } else {
+                "`copy-to-clipboard = false` in the config"
+            }
+        );
+        None
+    };
 
-    loop {
-        print!(">>> ");
-        std::io::stdout().flush()?;
-        let mut input = String::new();
-        std::io::stdin().read_line(&mut input)?;
+    if args.watch {
+        let write_back = cb.is_some();
+        let mut cb = match cb {
+            Some(cb) => cb,
+            None => Box::new(SystemClipboard::new(args.clipboard_target)?),
+        };
 
-        if quit_words.contains(input.trim()) {
-            break Ok(());
+        watch(
+            cb.as_mut(),
+            &chain,
+            write_back,
+            std::time::Duration::from_millis(args.watch_interval),
+            &mut seeded_rng(args.seed),
+        )?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let text = if let Some(path) = &args.input_file {
+        let result = std::fs::read_to_string(path);
+
+        let Ok(text) = result else {
+            match result.unwrap_err().kind() {
+                ErrorKind::NotFound => eprintln!("Could not find `{}`", path),
+                _ => eprintln!("Could not read `{}`", path),
+            }
+
+            return Ok(Failure::General.into());
+        };
+
+        Some(text)
+    } else if args.from_clipboard {
+        let text = match &mut cb {
+            Some(cb) => cb.get_text()?,
+            None => SystemClipboard::new(args.clipboard_target)?.get_text()?,
+        };
+
+        Some(text)
+    } else {
+        args.text
+    };
+
+    let text = match text {
+        Some(text) => Some(text),
+        None if !args.repl && !std::io::stdin().is_terminal() => {
+            Some(std::io::read_to_string(std::io::stdin())?)
         }
+        None => None,
+    };
 
-        let translated = translate(&input, language);
-        println!("{translated}");
+    if let Some(text) = &text
+        && args.count_only
+    {
+        println!("{}", language.count_matches(text));
+        return Ok(ExitCode::SUCCESS);
+    }
 
-        if let Some(ref mut cb) = cb {
-            cb.set_text(&translated)?;
+    if let Some(text) = &text
+        && args.explain
+    {
+        explain(
+            text,
+            &chain,
+            &mut seeded_rng(args.seed),
+            &mut std::io::stdout(),
+        )?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(text) = &text
+        && args.dry_run
+    {
+        dry_run(
+            text,
+            &chain,
+            &mut seeded_rng(args.seed),
+            &mut std::io::stdout(),
+        )?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    if let Some(text) = &text
+        && args.strict
+    {
+        let missing = untranslated_words(text, language);
+
+        if !missing.is_empty() {
+            eprintln!("Untranslated words: {}", missing.join(", "));
+            return Ok(Failure::Untranslated.into());
         }
     }
-}
 
-fn translate(text: &str, language: &Language) -> String {
-    let text = text.trim();
-    let mut text = text.to_string();
+    if let Some(text) = text {
+        let mut rng = seeded_rng(args.seed);
+        let translating = Instant::now();
+        let (mut translated, substitutions) = match &only_matching {
+            // Unmatched lines are kept as they are, blank or not, so only the
+            // surroundings --no-trim puts back are taken off.
+            Some(filter) => {
+                let text = if args.no_trim {
+                    trim_blank_lines(&text)
+                } else {
+                    &text
+                };
+                (translate_lines(text, filter, &chain, &mut rng), Vec::new())
+            }
+            None if args.json_values => {
+                match translate_json(&text, &args.json_path, &chain, &mut rng) {
+                    Ok(translated) => (translated, Vec::new()),
+                    Err(e) => {
+                        eprintln!("Could not parse the input as JSON: {e}");
+                        return Ok(Failure::General.into());
+                    }
+                }
+            }
+            None if args.markdown => (translate_markdown(&text, &chain, &mut rng), Vec::new()),
+            None => translate_chain_matches(&text, &chain, &mut rng),
+        };
+
+        if args.no_trim {
+            translated = untrimmed(&text, &translated);
+        }
+        report_time(args.time, "Translating", translating);
+
+        let output = match args.format {
+            OutputFormat::Text => translated.clone(),
+            OutputFormat::Json => serde_json::to_string(&Report {
+                original: trim_blank_lines(&text),
+                translated: &translated,
+                language: &chain_name(&chain),
+                substitutions: &substitutions,
+            })?,
+        };
+
+        let contents = if args.no_trim {
+            output.clone()
+        } else if args.in_place || args.output_file.is_some() {
+            with_trailing_newlines(&text, &output)
+        } else {
+            format!("{output}\n")
+        };
+        let contents = match encode(
+            &contents,
+            args.output_encoding.unwrap_or(encoding_rs::UTF_8),
+        ) {
+            Ok(contents) => contents,
+            Err(e) => {
+                eprintln!("Could not encode the translation: {e}");
+                return Ok(Failure::General.into());
+            }
+        };
 
-    match language.lower_mode {
-        CapitalizationMode::Lower => {
-            text = text.to_lowercase();
-            for (word, translation) in &language.dict {
-                text = text.replace(&word.to_lowercase(), &translation.to_lowercase());
+        if args.in_place
+            && let Some(path) = &args.input_file
+        {
+            if let Err(e) = replace_file(Path::new(path), &contents, args.backup) {
+                eprintln!("Could not write `{}`: {}", path, e);
+                return Ok(Failure::General.into());
             }
+        } else if let Some(path) = &args.output_file {
+            std::fs::write(path, contents)?;
+        } else {
+            std::io::stdout().write_all(&contents)?;
         }
-        CapitalizationMode::Upper => {
-            text = text.to_uppercase();
-            for (word, translation) in &language.dict {
-                text = text.replace(&word.to_uppercase(), &translation.to_uppercase());
+
+        if args.report_missing {
+            let missing = untranslated_words(&text, language);
+
+            if missing.is_empty() {
+                eprintln!("Every word is in the dictionary");
+            } else {
+                eprintln!("Untranslated words: {}", missing.join(", "));
             }
         }
-        CapitalizationMode::Preserve => {
-            for (word, translation) in &language.dict {
-                let lower_word = word.to_lowercase();
 
-                let matches: Vec<usize> = text
-                    .to_lowercase()
-                    .match_indices(&lower_word)
-                    .map(|(pos, _)| pos)
-                    .collect();
+        if args.stats {
+            let stats = stats(&text, language);
+            eprintln!("Words: {}", stats.words);
+            eprintln!("Matches: {}", stats.matches);
+            eprintln!("Distinct entries: {}", stats.distinct_entries);
+            eprintln!("Unmatched words: {}", stats.unmatched_words);
+        }
+
+        if let Some(cb) = &mut cb {
+            copy_and_wait(
+                cb.as_mut(),
+                &translated,
+                std::time::Duration::from_millis(args.clipboard_wait),
+            )?;
+        }
+
+        return Ok(ExitCode::SUCCESS);
+    } else if args.repl {
+        let mut repl = Repl {
+            config_paths: paths.to_vec(),
+            languages: config.languages.clone(),
+            chain: chain.clone(),
+            rules: args.rule,
+            capitalization: args.capitalization.clone(),
+            reverse: args.reverse,
+            cb,
+            previous_clipboard: None,
+            multiline: None,
+            quit_words: quit_words(config.global.as_ref()),
+            prompt: repl_prompt(config.global.as_ref()),
+            color: std::io::stdout().is_terminal(),
+            quiet: args.quiet,
+            trim: !args.no_trim,
+            max_replacements: args.max_replacements,
+            learning: args.learn.then(Learning::default),
+            rng: seeded_rng(args.seed),
+        };
 
-                for &pos in matches.iter().rev() {
-                    let end_pos = pos + word.len();
-                    let original_segment = &text[pos..end_pos];
+        if std::io::stdin().is_terminal() {
+            let history = history_path(args.history_file);
+            let mut editor = open_editor(history.as_deref())?;
 
-                    let replacement = if original_segment.to_lowercase() == original_segment {
-                        translation.to_lowercase()
-                    } else if original_segment.to_uppercase() == original_segment
-                        && text.to_uppercase() == text
-                    {
-                        translation.to_uppercase()
-                    } else {
-                        let mut c = translation.chars();
-                        match c.next() {
-                            None => String::new(),
-                            Some(f) => f.to_uppercase().chain(c).collect(),
-                        }
-                    };
+            repl.run_interactive(&mut editor, &mut std::io::stdout(), &mut std::io::stderr())?;
 
-                    text.replace_range(pos..end_pos, &replacement);
+            if let Some(path) = &history {
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent)?;
                 }
+
+                editor.save_history(path)?;
             }
+        } else {
+            repl.run(
+                &mut std::io::stdin().lock(),
+                &mut std::io::stdout(),
+                &mut std::io::stderr(),
+            )?;
         }
-    };
 
-    text
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    Ok(ExitCode::SUCCESS)
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// The state of an interactive translation session.
+struct Repl {
+    /// The config files `:reload` reads, none if the languages didn't come
+    /// from a config.
+    config_paths: Vec<String>,
+    languages: Vec<Language>,
+    /// The languages the input is translated through, in order.
+    chain: Vec<CompiledLanguage>,
+    /// The `--rule` entries, added to the first language of every chain.
+    rules: Vec<(String, String)>,
+    /// The `--capitalization` mode, used by every language instead of its own.
+    capitalization: Option<CapitalizationMode>,
+    reverse: bool,
+    cb: Option<Box<dyn ClipboardSink>>,
+    /// What the clipboard held before the last translation was copied to it,
+    /// `None` inside if it couldn't be read. `:undo` puts it back.
+    previous_clipboard: Option<Option<String>>,
+    /// The input entered so far when it spans several lines.
+    multiline: Option<Multiline>,
+    quit_words: QuitWords,
+    /// The template of the prompt, see [`render_prompt`].
+    prompt: String,
+    /// Whether to highlight the substituted words in the output.
+    color: bool,
+    /// Whether to leave out the banner and other informational messages.
+    quiet: bool,
+    /// Whether to drop the whitespace around each line, like translating does.
+    trim: bool,
+    max_replacements: Option<usize>,
+    /// The words still to learn, if the session adds unknown words to the
+    /// config.
+    learning: Option<Learning>,
+    rng: StdRng,
+}
 
-    #[test]
-    fn test_translate() {
-        let language = Language {
-            name: "test".to_string(),
-            lower_mode: CapitalizationMode::Lower,
-            dict: vec![
-                ("hello".to_string(), "hola".to_string()),
-                ("world".to_string(), "mundo".to_string()),
-            ]
-            .into_iter()
-            .collect(),
+/// An input of several lines that is still being entered.
+#[derive(Debug, Default)]
+struct Multiline {
+    text: String,
+    /// Whether it was started with `:paste` and ends at `:end`, rather than at
+    /// the first line that doesn't end in a backslash.
+    pasted: bool,
+}
+
+/// The unknown words of a `--learn` session.
+#[derive(Debug, Default)]
+struct Learning {
+    /// The words to ask the translation of, the first one being asked now.
+    unknown: VecDeque<String>,
+    /// The lower cased words that were skipped, which are not asked again.
+    skipped: HashSet<String>,
+}
+
+impl Repl {
+    /// Runs the REPL on plain line-based input, such as a pipe, writing
+    /// errors to `errors`. The session ends at the end of the input.
+    fn run(
+        &mut self,
+        input: &mut impl BufRead,
+        output: &mut impl Write,
+        errors: &mut impl Write,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.banner(output)?;
+
+        loop {
+            write!(output, "{}", self.prompt())?;
+            output.flush()?;
+            let mut line = String::new();
+
+            if input.read_line(&mut line)? == 0 {
+                writeln!(output)?;
+                self.inform(output, "Goodbye!")?;
+                break Ok(());
+            }
+
+            if !self.handle_line(&line, output, errors)? {
+                break Ok(());
+            }
+        }
+    }
+
+    /// Runs the REPL on a terminal, with line editing and history, writing
+    /// errors to `errors`. Ctrl-C and Ctrl-D end the session.
+    fn run_interactive(
+        &mut self,
+        editor: &mut ReplEditor,
+        output: &mut impl Write,
+        errors: &mut impl Write,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.banner(output)?;
+
+        loop {
+            if editor
+                .helper()
+                .is_none_or(|helper| helper.language != self.chain[0].name)
+            {
+                editor.set_helper(Some(DictCompleter::new(&self.chain[0])));
+            }
+
+            let line = match editor.readline(&self.prompt()) {
+                Ok(line) => line,
+                Err(ReadlineError::Eof | ReadlineError::Interrupted) => {
+                    self.inform(output, "Goodbye!")?;
+                    break Ok(());
+                }
+                Err(e) => break Err(e.into()),
+            };
+
+            editor.add_history_entry(line.as_str())?;
+
+            if !self.handle_line(&line, output, errors)? {
+                break Ok(());
+            }
+        }
+    }
+
+    fn banner(&self, output: &mut impl Write) -> std::io::Result<()> {
+        if self.quiet {
+            return Ok(());
+        }
+
+        writeln!(output, "Welcome to {} REPL", chain_name(&self.chain))?;
+        if self.quit_words.words.is_empty() {
+            writeln!(output, "Press Ctrl-D to exit")?;
+        } else {
+            let mut words: Vec<String> = self
+                .quit_words
+                .words
+                .iter()
+                .map(|w| format!("\"{w}\""))
+                .collect();
+            words.sort();
+            writeln!(output, "Type any of {} to exit", words.join(", "))?;
+        }
+        writeln!(output, "Type \":lang <name>\" to switch languages")?;
+
+        if !self.config_paths.is_empty() {
+            writeln!(output, "Type \":reload\" to reload the config")?;
+        }
+
+        writeln!(
+            output,
+            "End a line with \"\\\" or type \":paste\" to enter several lines"
+        )?;
+
+        if self.cb.is_some() {
+            writeln!(output, "Type \":undo\" to restore the clipboard")?;
+        }
+
+        if self.learning.is_some() {
+            writeln!(
+                output,
+                "Unknown words are added to the config as you translate them"
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the prompt for the next line, which names the unknown word it
+    /// asks the translation of while learning.
+    fn prompt(&self) -> String {
+        if self.multiline.is_some() {
+            return "... ".to_string();
+        }
+
+        match self.learning.as_ref().and_then(|l| l.unknown.front()) {
+            Some(word) => format!("{word} = "),
+            None => render_prompt(&self.prompt, &self.chain),
+        }
+    }
+
+    /// Writes an informational message to `output`, unless the REPL is quiet.
+    fn inform(
+        &self,
+        output: &mut impl Write,
+        message: impl std::fmt::Display,
+    ) -> std::io::Result<()> {
+        if self.quiet {
+            return Ok(());
+        }
+
+        writeln!(output, "{message}")
+    }
+
+    /// Handles one line of input, writing what went wrong to `errors`.
+    /// Returns `false` once the session should end.
+    fn handle_line(
+        &mut self,
+        line: &str,
+        output: &mut impl Write,
+        errors: &mut impl Write,
+    ) -> Result<bool, Box<dyn std::error::Error>> {
+        if self.multiline.is_some() {
+            self.continue_multiline(line, output)?;
+            return Ok(true);
+        }
+
+        if self.quit_words.matches(line) {
+            return Ok(false);
+        }
+
+        if let Some(word) = self.learning.as_mut().and_then(|l| l.unknown.pop_front()) {
+            self.learn(word, line.trim(), output, errors)?;
+            return Ok(true);
+        }
+
+        if let Some(name) = line.trim().strip_prefix(":lang ") {
+            self.switch_language(name.trim(), output, errors)?;
+            return Ok(true);
+        }
+
+        if line.trim() == ":reload" {
+            self.reload(output, errors)?;
+            return Ok(true);
+        }
+
+        if line.trim() == ":undo" {
+            self.undo(output, errors)?;
+            return Ok(true);
+        }
+
+        if line.trim() == ":paste" {
+            self.multiline = Some(Multiline {
+                text: String::new(),
+                pasted: true,
+            });
+            self.inform(
+                output,
+                "Type \":end\" on its own line to translate the text",
+            )?;
+            return Ok(true);
+        }
+
+        if let Some(start) = line.trim_end_matches(['\r', '\n']).strip_suffix('\\') {
+            self.multiline = Some(Multiline {
+                text: format!("{start}\n"),
+                pasted: false,
+            });
+            return Ok(true);
+        }
+
+        self.translate(line, output)?;
+
+        Ok(true)
+    }
+
+    /// Adds `line` to the input of several lines being entered, translating
+    /// it as one text once it ends.
+    fn continue_multiline(
+        &mut self,
+        line: &str,
+        output: &mut impl Write,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(multiline) = &mut self.multiline else {
+            return Ok(());
         };
+        let line = line.trim_end_matches(['\r', '\n']);
+
+        match (multiline.pasted, line.strip_suffix('\\')) {
+            (true, _) if line.trim() == ":end" => {
+                multiline.text.pop();
+            }
+            (true, _) => {
+                multiline.text.push_str(line);
+                multiline.text.push('\n');
+                return Ok(());
+            }
+            (false, Some(line)) => {
+                multiline.text.push_str(line);
+                multiline.text.push('\n');
+                return Ok(());
+            }
+            (false, None) => multiline.text.push_str(line),
+        }
+
+        let text = self.multiline.take().unwrap_or_default().text;
+        self.translate(&text, output)
+    }
+
+    /// Translates `line`, which may span several lines, writes the result to
+    /// `output` and copies it to the clipboard.
+    fn translate(
+        &mut self,
+        line: &str,
+        output: &mut impl Write,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (mut translated, mut spans) = translate_chain_spans(line, &self.chain, &mut self.rng);
+
+        if !self.trim {
+            let line = line.trim_end_matches(['\r', '\n']);
+            let leading = trimmed_range(line).start;
+            translated = untrimmed(line, &translated);
+            for span in &mut spans {
+                *span = span.start + leading..span.end + leading;
+            }
+        }
+
+        if self.color {
+            writeln!(output, "{}", highlight(&translated, &spans))?;
+        } else {
+            writeln!(output, "{translated}")?;
+        }
+
+        if let Some(cb) = &mut self.cb {
+            self.previous_clipboard = Some(cb.get_text().ok());
+            cb.set_text(&translated)?;
+        }
+
+        if let Some(learning) = &mut self.learning {
+            for word in untranslated_words(line, &self.chain[0]) {
+                let lower = word.to_lowercase();
+
+                if !learning.skipped.contains(&lower)
+                    && !learning.unknown.iter().any(|w| w.to_lowercase() == lower)
+                {
+                    learning.unknown.push_back(word);
+                }
+            }
+
+            if !learning.unknown.is_empty() {
+                self.inform(
+                    output,
+                    "Type the translation of each unknown word, or nothing to skip it",
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Puts back what the clipboard held before the last translation was
+    /// copied to it. Only that one copy can be undone.
+    fn undo(
+        &mut self,
+        output: &mut impl Write,
+        errors: &mut impl Write,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(cb) = &mut self.cb else {
+            writeln!(errors, "There is no clipboard to restore")?;
+            return Ok(());
+        };
+
+        match self.previous_clipboard.take() {
+            Some(Some(previous)) => {
+                cb.set_text(&previous)?;
+                self.inform(output, "Restored the clipboard")?;
+            }
+            Some(None) => writeln!(
+                errors,
+                "Could not restore the clipboard, it couldn't be read before the last translation"
+            )?,
+            None => writeln!(errors, "Nothing to undo")?,
+        }
+
+        Ok(())
+    }
+
+    /// Adds `word` with `translation` to the first active language, both in
+    /// the session and in its config file. An empty `translation` skips the
+    /// word for the rest of the session, as does failing to save it.
+    fn learn(
+        &mut self,
+        word: String,
+        translation: &str,
+        output: &mut impl Write,
+        errors: &mut impl Write,
+    ) -> std::io::Result<()> {
+        let name = self.chain[0].name.clone();
+        let saved = match translation {
+            "" => None,
+            _ => match self.save_entry(&name, &word, translation) {
+                Ok(path) => Some(path),
+                Err(e) => {
+                    writeln!(errors, "Could not add `{word}`: {e}")?;
+                    None
+                }
+            },
+        };
+
+        let Some(path) = saved else {
+            if let Some(learning) = &mut self.learning {
+                learning.skipped.insert(word.to_lowercase());
+            }
+            return Ok(());
+        };
+
+        if let Some(language) = self.languages.iter_mut().find(|l| l.name == name) {
+            language
+                .dict
+                .insert(word.clone(), Translation::Single(translation.to_string()));
+        }
+
+        if let Ok(chain) = resolve_chain(&self.languages, &self.chain_names(), self.reverse) {
+            self.chain = self.compile(chain);
+        }
+
+        self.inform(output, format_args!("Added `{word}` to `{path}`"))
+    }
+
+    /// Adds the entry to the last config file that defines `language`, the
+    /// definition that is used, keeping the rest of the file as it is. Returns
+    /// the path of that file.
+    fn save_entry(
+        &self,
+        language: &str,
+        key: &str,
+        translation: &str,
+    ) -> Result<String, Box<dyn std::error::Error>> {
+        for path in self.config_paths.iter().rev() {
+            let contents = std::fs::read_to_string(path)?;
+
+            if !parse_config(path, &contents)?
+                .languages
+                .iter()
+                .any(|l| l.name == language)
+            {
+                continue;
+            }
+
+            if matches!(
+                Path::new(path).extension().and_then(|ext| ext.to_str()),
+                Some("json" | "yaml" | "yml")
+            ) {
+                return Err(format!("`{path}` is not a TOML file").into());
+            }
+
+            let mut config: ConfigWriter = contents.parse()?;
+            config.set_dict_entry(language, key, translation)?;
+            replace_file(Path::new(path), config.to_string().as_bytes(), false)?;
+
+            return Ok(path.clone());
+        }
+
+        Err(format!("no config file defines `{language}`").into())
+    }
+
+    /// Returns the names of the active languages, separated by commas like
+    /// `--language`.
+    fn chain_names(&self) -> String {
+        // A reversed chain is stored back to front.
+        let mut names: Vec<&str> = self.chain.iter().map(|l| l.name.as_str()).collect();
+        if self.reverse {
+            names.reverse();
+        }
+
+        names.join(",")
+    }
+
+    /// Compiles a chain resolved from the languages of the session, with the
+    /// `--rule` entries and `--capitalization` mode applied.
+    fn compile(&self, mut chain: Vec<Language>) -> Vec<CompiledLanguage> {
+        add_rules(&mut chain, &self.rules);
+        override_capitalization(&mut chain, self.capitalization.as_ref());
+        compile_chain(chain, self.max_replacements)
+    }
+
+    /// Makes the language called `name`, or the comma-separated chain of
+    /// languages, the active one. The current languages stay active if any of
+    /// them does not exist.
+    fn switch_language(
+        &mut self,
+        name: &str,
+        output: &mut impl Write,
+        errors: &mut impl Write,
+    ) -> std::io::Result<()> {
+        match resolve_chain(&self.languages, name, self.reverse) {
+            Ok(chain) => {
+                self.chain = self.compile(chain);
+                self.inform(
+                    output,
+                    format_args!("Switched to {}", chain_name(&self.chain)),
+                )
+            }
+            Err(name) => writeln!(errors, "{}", language_not_found(&self.languages, &name)),
+        }
+    }
+
+    /// Reads the config files again and picks up the new definitions of the
+    /// active languages. Everything stays as it was if that fails.
+    fn reload(&mut self, output: &mut impl Write, errors: &mut impl Write) -> std::io::Result<()> {
+        if self.config_paths.is_empty() {
+            return writeln!(errors, "There is no config to reload");
+        }
+
+        let config = match load_config(&self.config_paths) {
+            Ok(config) => config,
+            Err(e) => {
+                writeln!(errors, "{e}")?;
+                return writeln!(errors, "Kept the previous config");
+            }
+        };
+
+        match resolve_chain(&config.languages, &self.chain_names(), self.reverse) {
+            Ok(chain) => {
+                self.chain = self.compile(chain);
+                self.languages = config.languages;
+                self.inform(output, "Reloaded the config")
+            }
+            Err(name) => {
+                writeln!(errors, "Language {name} is no longer defined")?;
+                writeln!(errors, "Kept the previous config")
+            }
+        }
+    }
+}
+
+/// The REPL prompt when the config doesn't set `repl-prompt`.
+const DEFAULT_REPL_PROMPT: &str = "{language}({mode})>>> ";
+
+/// Returns the template of the REPL prompt: the configured `repl-prompt`, or
+/// [`DEFAULT_REPL_PROMPT`] if there is none.
+fn repl_prompt(global: Option<&Global>) -> String {
+    global
+        .and_then(|g| g.repl_prompt.clone())
+        .unwrap_or_else(|| DEFAULT_REPL_PROMPT.to_string())
+}
+
+/// Fills in the prompt `template` for `chain`: `{language}` becomes the names
+/// of its languages and `{mode}` the capitalization mode of the last one,
+/// which decides the case of the translation.
+fn render_prompt(template: &str, chain: &[CompiledLanguage]) -> String {
+    let mode = chain
+        .last()
+        .map(|l| l.lower_mode.to_string())
+        .unwrap_or_default();
+
+    template
+        .replace("{language}", &chain_name(chain))
+        .replace("{mode}", &mode)
+}
+
+/// The words that end the REPL when the config doesn't set `quit-keywords`.
+const DEFAULT_QUIT_KEYWORDS: [&str; 3] = ["quit", "exit", ":q"];
+
+/// Returns the words that end the REPL: the configured `quit-keywords`, or
+/// [`DEFAULT_QUIT_KEYWORDS`] if there are none. They match regardless of case
+/// unless `case-sensitive-quit` is set.
+fn quit_words(global: Option<&Global>) -> QuitWords {
+    let words = match global.and_then(|g| g.quit_keywords.as_ref()) {
+        Some(words) => words.clone(),
+        None => DEFAULT_QUIT_KEYWORDS.map(String::from).into(),
+    };
+    let case_sensitive = global.and_then(|g| g.case_sensitive_quit) == Some(true);
+
+    QuitWords::new(words, case_sensitive)
+}
+
+/// The words that end the REPL session.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct QuitWords {
+    /// The words, lower cased unless matching is case-sensitive.
+    words: HashSet<String>,
+    case_sensitive: bool,
+}
+
+impl QuitWords {
+    fn new(words: impl IntoIterator<Item = String>, case_sensitive: bool) -> Self {
+        Self {
+            words: words
+                .into_iter()
+                .map(|word| Self::normalize(&word, case_sensitive))
+                .collect(),
+            case_sensitive,
+        }
+    }
+
+    fn normalize(word: &str, case_sensitive: bool) -> String {
+        if case_sensitive {
+            word.trim().to_string()
+        } else {
+            word.trim().to_lowercase()
+        }
+    }
+
+    /// Returns whether the input `line` is one of the words.
+    fn matches(&self, line: &str) -> bool {
+        self.words
+            .contains(&Self::normalize(line, self.case_sensitive))
+    }
+}
+
+/// Resolves the `--history-file` argument: no history without the flag, the
+/// given path if there is one, and a file in the cache directory otherwise.
+fn history_path(arg: Option<Option<String>>) -> Option<PathBuf> {
+    match arg? {
+        Some(path) => Some(path.into()),
+        None => {
+            ProjectDirs::from("", "", "tranclator").map(|dirs| dirs.cache_dir().join("history"))
+        }
+    }
+}
+
+type ReplEditor = Editor<DictCompleter, DefaultHistory>;
+
+/// Creates a line editor, loading the history at `history` if it exists.
+fn open_editor(history: Option<&Path>) -> rustyline::Result<ReplEditor> {
+    let mut editor = ReplEditor::new()?;
+
+    if let Some(path) = history
+        && path.exists()
+    {
+        editor.load_history(path)?;
+    }
+
+    Ok(editor)
+}
+
+/// Completes the word under the cursor with the source words of a language.
+struct DictCompleter {
+    language: String,
+    words: Vec<String>,
+}
+
+impl DictCompleter {
+    fn new(language: &Language) -> Self {
+        Self {
+            language: language.name.clone(),
+            words: language
+                .dict
+                .keys()
+                .filter(|key| !key.starts_with(PATTERN_PREFIX))
+                .map(|key| strip_anchors(key).0.to_string())
+                .collect(),
+        }
+    }
+
+    /// Returns where the whitespace-separated token ending at `pos` starts,
+    /// along with every word that starts with that token, ignoring case.
+    fn complete_word(&self, line: &str, pos: usize) -> (usize, Vec<String>) {
+        let start = line[..pos].rfind(char::is_whitespace).map_or(0, |i| {
+            i + line[i..].chars().next().map_or(1, char::len_utf8)
+        });
+        let prefix = line[start..pos].to_lowercase();
+
+        if prefix.is_empty() {
+            return (start, Vec::new());
+        }
+
+        let candidates = self
+            .words
+            .iter()
+            .filter(|word| word.to_lowercase().starts_with(&prefix))
+            .cloned()
+            .collect();
+
+        (start, candidates)
+    }
+}
+
+impl Completer for DictCompleter {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        Ok(self.complete_word(line, pos))
+    }
+}
+
+impl Hinter for DictCompleter {
+    type Hint = String;
+}
+
+impl Highlighter for DictCompleter {}
+
+impl Validator for DictCompleter {}
+
+impl Helper for DictCompleter {}
+
+/// Polls `cb` every `interval` and prints the translation of every new text
+/// that shows up, writing it back to `cb` if `write_back` is set. Whatever is
+/// on the clipboard when watching starts is left alone.
+fn watch(
+    cb: &mut dyn ClipboardSink,
+    chain: &[CompiledLanguage],
+    write_back: bool,
+    interval: std::time::Duration,
+    rng: &mut dyn Rng,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let mut watcher = Watcher {
+        last_seen: cb.get_text().ok(),
+    };
+
+    loop {
+        if let Some(translated) = watcher.poll(cb, chain, write_back, rng)? {
+            println!("{translated}");
+        }
+
+        std::thread::sleep(interval);
+    }
+}
+
+/// Remembers the last clipboard contents seen by [`watch`].
+struct Watcher {
+    last_seen: Option<String>,
+}
+
+impl Watcher {
+    /// Translates the clipboard contents if they changed since the last poll.
+    /// Translations written back to `cb` count as seen, so they are not
+    /// translated again on the next poll.
+    fn poll(
+        &mut self,
+        cb: &mut dyn ClipboardSink,
+        chain: &[CompiledLanguage],
+        write_back: bool,
+        rng: &mut dyn Rng,
+    ) -> Result<Option<String>, Box<dyn std::error::Error>> {
+        let Ok(current) = cb.get_text() else {
+            return Ok(None);
+        };
+
+        if self.last_seen.as_ref() == Some(&current) {
+            return Ok(None);
+        }
+
+        let translated = translate_chain(&current, chain, rng);
+
+        if write_back {
+            cb.set_text(&translated)?;
+            self.last_seen = Some(translated.clone());
+        } else {
+            self.last_seen = Some(current);
+        }
+
+        Ok(Some(translated))
+    }
+}
+
+/// Parses a `--rule` argument of the form `from=to`.
+fn parse_rule(rule: &str) -> Result<(String, String), String> {
+    let Some((from, to)) = rule.split_once('=') else {
+        return Err(format!(
+            "expected `from=to`, but there is no `=` in `{rule}`"
+        ));
+    };
+
+    if from.is_empty() {
+        return Err("the text to replace can't be empty".to_string());
+    }
+
+    Ok((from.to_string(), to.to_string()))
+}
+
+/// Makes every language of `chain` use `mode` instead of its own
+/// capitalization mode, if there is one.
+fn override_capitalization(chain: &mut [Language], mode: Option<&CapitalizationMode>) {
+    if let Some(mode) = mode {
+        for language in chain {
+            language.lower_mode = mode.clone();
+        }
+    }
+}
+
+/// Compiles every language of `chain`, limiting the translations through it
+/// to `max_replacements` replacements in all.
+fn compile_chain(chain: Vec<Language>, max_replacements: Option<usize>) -> Vec<CompiledLanguage> {
+    chain
+        .into_iter()
+        .map(|language| CompiledLanguage::new(language).with_max_replacements(max_replacements))
+        .collect()
+}
+
+/// Describes that the language `name` is not defined, suggesting the closest
+/// name in `languages` when it looks like a typo.
+fn language_not_found(languages: &[Language], name: &str) -> String {
+    match suggest_language(languages, name) {
+        Some(suggestion) => format!("Language {name} not found, did you mean `{suggestion}`?"),
+        None => format!("Language {name} not found"),
+    }
+}
+
+/// Prints how long `step`, which began at `started`, took to stderr if
+/// `enabled` is set.
+fn report_time(enabled: bool, step: &str, started: Instant) {
+    if enabled {
+        eprintln!("{step} took {:.2?}", started.elapsed());
+    }
+}
+
+/// Prints an informational message, one that is neither a translation nor an
+/// error, to stderr unless `quiet` is set, keeping stdout for translations.
+fn inform(quiet: bool, message: impl std::fmt::Display) {
+    if !quiet {
+        eprintln!("{message}");
+    }
+}
+
+/// Logs to stderr at a level set by how often `--verbose` was given, only for
+/// this crate so dependencies stay quiet. Warnings are logged unless `quiet` is
+/// set. `RUST_LOG` overrides both.
+fn init_logging(verbose: u8, quiet: bool) {
+    let level = match verbose {
+        _ if quiet => LevelFilter::Error,
+        0 => LevelFilter::Warn,
+        1 => LevelFilter::Info,
+        2 => LevelFilter::Debug,
+        _ => LevelFilter::Trace,
+    };
+
+    env_logger::Builder::new()
+        .filter_module("tranclator", level)
+        .format_timestamp(None)
+        .parse_default_env()
+        .init();
+}
+
+/// Checks that an `--only-matching` argument is a valid regular expression.
+fn parse_regex(pattern: &str) -> Result<String, regex::Error> {
+    Regex::new(pattern).map(|_| pattern.to_string())
+}
+
+/// Parses an `--output-encoding` label, refusing encodings that can only be
+/// read, such as UTF-16.
+fn parse_encoding(label: &str) -> Result<&'static Encoding, String> {
+    match Encoding::for_label(label.as_bytes()) {
+        Some(encoding) if encoding.output_encoding() == encoding => Ok(encoding),
+        Some(encoding) => Err(format!("{} can only be read, not written", encoding.name())),
+        None => Err(format!("unknown encoding `{label}`")),
+    }
+}
+
+/// Encodes `text` in `encoding`, failing on the first character it has no
+/// representation for.
+fn encode(text: &str, encoding: &'static Encoding) -> Result<Vec<u8>, String> {
+    let (bytes, _, unmappable) = encoding.encode(text);
+
+    if unmappable {
+        let c = text
+            .chars()
+            .find(|c| encoding.encode(c.encode_utf8(&mut [0; 4])).2)
+            .unwrap_or(char::REPLACEMENT_CHARACTER);
+        return Err(format!("`{c}` can't be represented in {}", encoding.name()));
+    }
+
+    Ok(bytes.into_owned())
+}
+
+/// Completes `--language` with the languages defined in the config files of the
+/// command line being completed. Config files that can't be read are skipped.
+fn language_candidates() -> Vec<CompletionCandidate> {
+    let args: Vec<String> = std::env::args().collect();
+    let mut config = Config::default();
+
+    for path in resolve_config_paths(
+        &config_paths_in(&args),
+        std::env::var(CONFIG_ENV).ok(),
+        &config_search_paths(),
+    ) {
+        if let Some(other) = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|contents| parse_config(&path, &contents).ok())
+        {
+            merge_config(&mut config, other);
+        }
+    }
+
+    language_names(&config)
+        .into_iter()
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+/// Returns every `--config-path` given in `args`.
+fn config_paths_in(args: &[String]) -> Vec<String> {
+    let mut paths = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if arg == "--config-path" {
+            paths.extend(args.next().cloned());
+        } else if let Some(path) = arg.strip_prefix("--config-path=") {
+            paths.push(path.to_string());
+        }
+    }
+
+    paths
+}
+
+/// Environment variable with the config path to use without `--config-path`.
+const CONFIG_ENV: &str = "TRANCLATOR_CONFIG";
+
+/// Returns where to look for a config file when none is given, in order:
+/// `tranclator.toml` in the working directory, then `config.toml` in the user's
+/// config directory, such as `~/.config/tranclator` on Linux.
+fn config_search_paths() -> Vec<PathBuf> {
+    let mut paths = vec![PathBuf::from("tranclator.toml")];
+    paths.extend(
+        ProjectDirs::from("", "", "tranclator").map(|dirs| dirs.config_dir().join("config.toml")),
+    );
+    paths
+}
+
+/// Decides which config files to load: the `--config-path`s if any were given,
+/// otherwise the path in [`CONFIG_ENV`], otherwise the first of `search` that
+/// exists. Falls back to the first of `search` if none of them do.
+fn resolve_config_paths(cli: &[String], env: Option<String>, search: &[PathBuf]) -> Vec<String> {
+    if !cli.is_empty() {
+        return cli.to_vec();
+    }
+
+    if let Some(path) = env.filter(|path| !path.is_empty()) {
+        return vec![path];
+    }
+
+    search
+        .iter()
+        .find(|path| path.exists())
+        .or(search.first())
+        .map(|path| path.to_string_lossy().into_owned())
+        .into_iter()
+        .collect()
+}
+
+/// Replaces the contents of the file at `path` with `contents`. They are
+/// written to a file next to it first, which is then renamed over it, so a
+/// failed write leaves the file as it was. With `backup`, the original is kept
+/// with `.bak` appended to its name.
+fn replace_file(path: &Path, contents: &[u8], backup: bool) -> std::io::Result<()> {
+    let with_suffix = |suffix: &str| {
+        let mut name = path.as_os_str().to_owned();
+        name.push(suffix);
+        PathBuf::from(name)
+    };
+    let temp = with_suffix(".tranclator.tmp");
+
+    let result = std::fs::write(&temp, contents)
+        .and_then(|()| std::fs::set_permissions(&temp, std::fs::metadata(path)?.permissions()))
+        .and_then(|()| {
+            if backup {
+                std::fs::copy(path, with_suffix(".bak"))?;
+            }
+
+            std::fs::rename(&temp, path)
+        });
+
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temp);
+    }
+
+    result
+}
+
+/// Returns where the translation of the file at `path` is written to when
+/// translating several files: next to it, with the extension `.out`.
+fn batch_output_path(path: &Path) -> PathBuf {
+    let output = path.with_extension("out");
+
+    if output == path {
+        let mut name = path.as_os_str().to_owned();
+        name.push(".out");
+        PathBuf::from(name)
+    } else {
+        output
+    }
+}
+
+/// Translates the file at `input` through `chain` and writes the result to
+/// `output`. With `filter`, only the lines matching it are translated. Fails
+/// with [`ErrorKind::InvalidData`] if `input` is not a text file.
+fn translate_file(
+    input: &Path,
+    output: &Path,
+    filter: Option<&Regex>,
+    chain: &[CompiledLanguage],
+    rng: &mut dyn Rng,
+) -> std::io::Result<()> {
+    let text = std::fs::read_to_string(input)?;
+
+    if text.contains('\0') {
+        return Err(std::io::Error::new(
+            ErrorKind::InvalidData,
+            "file contains NUL bytes",
+        ));
+    }
+
+    let translated = match filter {
+        Some(filter) => translate_lines(&text, filter, chain, rng),
+        None => translate_chain(&text, chain, rng),
+    };
+
+    std::fs::write(output, with_trailing_newlines(&text, &translated))
+}
+
+/// Translates the prose of the Markdown document `text` through `chain`,
+/// copying everything else as it is, see [`markdown_prose`]. Each language
+/// cases the document and puts its prefix and suffix around it once.
+fn translate_markdown(text: &str, chain: &[CompiledLanguage], rng: &mut dyn Rng) -> String {
+    let text = trim_blank_lines(text);
+    translate_chain_fragments(text, &markdown_prose(text), chain, rng)
+}
+
+/// Returns the byte ranges of the prose in the Markdown document `text`: its
+/// text outside code blocks and autolinks. Inline code, HTML and link URLs
+/// are not text to begin with. Runs of text separated by nothing but
+/// whitespace, such as a line break, form one range so phrases match across
+/// them.
+fn markdown_prose(text: &str) -> Vec<Range<usize>> {
+    let options = Options::ENABLE_TABLES | Options::ENABLE_STRIKETHROUGH;
+    let mut ranges: Vec<Range<usize>> = Vec::new();
+    // The end of the code block or autolink being skipped.
+    let mut skip_until = 0;
+
+    for (event, range) in pulldown_cmark::Parser::new_ext(text, options).into_offset_iter() {
+        match event {
+            Event::Start(
+                Tag::CodeBlock(_)
+                | Tag::Link {
+                    link_type: LinkType::Autolink | LinkType::Email,
+                    ..
+                },
+            ) => skip_until = range.end,
+            Event::Text(_) if range.start >= skip_until => match ranges.last_mut() {
+                Some(last) if text[last.end..range.start].trim().is_empty() => {
+                    last.end = range.end;
+                }
+                _ => ranges.push(range),
+            },
+            _ => {}
+        }
+    }
+
+    ranges
+}
+
+/// Translates the string values of the JSON document `text` through `chain`,
+/// keeping its keys and structure, and prints it again. With `paths`, only the
+/// strings at or below one of them are translated, see [`json_path_matches`].
+fn translate_json(
+    text: &str,
+    paths: &[String],
+    chain: &[CompiledLanguage],
+    rng: &mut dyn Rng,
+) -> serde_json::Result<String> {
+    let mut value: serde_json::Value = serde_json::from_str(text)?;
+    let paths: Vec<Vec<&str>> = paths
+        .iter()
+        .map(|path| {
+            let path = path.strip_prefix('$').unwrap_or(path);
+            match path.strip_prefix('.').unwrap_or(path) {
+                "" => Vec::new(),
+                path => path.split('.').collect(),
+            }
+        })
+        .collect();
+
+    translate_json_value(&mut value, &mut Vec::new(), &paths, chain, rng);
+
+    serde_json::to_string_pretty(&value)
+}
+
+/// Translates the strings in `value`, found at `path` in the document, that
+/// lie at or below one of `paths`, or all of them if there are no `paths`.
+fn translate_json_value(
+    value: &mut serde_json::Value,
+    path: &mut Vec<String>,
+    paths: &[Vec<&str>],
+    chain: &[CompiledLanguage],
+    rng: &mut dyn Rng,
+) {
+    match value {
+        serde_json::Value::String(text)
+            if paths.is_empty() || paths.iter().any(|p| json_path_matches(p, path)) =>
+        {
+            *text = translate_chain(text, chain, rng);
+        }
+        serde_json::Value::Array(items) => {
+            for (i, item) in items.iter_mut().enumerate() {
+                path.push(i.to_string());
+                translate_json_value(item, path, paths, chain, rng);
+                path.pop();
+            }
+        }
+        serde_json::Value::Object(fields) => {
+            for (key, item) in fields.iter_mut() {
+                path.push(key.clone());
+                translate_json_value(item, path, paths, chain, rng);
+                path.pop();
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Returns whether the JSON value at `path` lies at or below the dotted
+/// `pattern`, whose `*` segments match any key or index.
+fn json_path_matches(pattern: &[&str], path: &[String]) -> bool {
+    pattern.len() <= path.len()
+        && pattern
+            .iter()
+            .zip(path)
+            .all(|(segment, key)| *segment == "*" || segment == key)
+}
+
+/// Translates the lines of `text` that match `filter` through `chain` and
+/// keeps the other lines as they are. Like [`translate_chain`], the line breaks
+/// that end `text` are trimmed.
+fn translate_lines(
+    text: &str,
+    filter: &Regex,
+    chain: &[CompiledLanguage],
+    rng: &mut dyn Rng,
+) -> String {
+    let mut translated = String::with_capacity(text.len());
+
+    for line in text.split_inclusive('\n') {
+        let content = line.trim_end_matches(['\r', '\n']);
+
+        if filter.is_match(content) {
+            let content = content.trim_end();
+            translated.push_str(&translate_chain(content, chain, rng));
+            translated.push_str(&line[content.len()..]);
+        } else {
+            translated.push_str(line);
+        }
+    }
+
+    translated.truncate(translated.trim_end_matches(['\r', '\n']).len());
+    translated
+}
+
+/// Translates every file under `input` whose path relative to it matches
+/// `glob` into the same place under `output`, reporting each file unless
+/// `quiet` is set. With `filter`, only the lines matching it are translated.
+/// Files that are not text are skipped. Returns whether any file could not be
+/// translated.
+fn translate_tree(
+    input: &Path,
+    output: &Path,
+    glob: Option<&GlobMatcher>,
+    filter: Option<&Regex>,
+    chain: &[CompiledLanguage],
+    rng: &mut dyn Rng,
+    quiet: bool,
+) -> std::io::Result<bool> {
+    let mut failed = false;
+
+    for entry in WalkDir::new(input).sort_by_file_name() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                eprintln!("Could not read `{}`: {}", input.display(), e);
+                failed = true;
+                continue;
+            }
+        };
+        let path = entry.path();
+
+        // The output may be inside the input when `--output-dir` says so.
+        if !entry.file_type().is_file() || path.starts_with(output) {
+            continue;
+        }
+
+        let relative = path.strip_prefix(input).unwrap_or(path);
+        if glob.is_some_and(|glob| !glob.is_match(relative)) {
+            continue;
+        }
+
+        let target = output.join(relative);
+        let result = match target.parent() {
+            Some(parent) => std::fs::create_dir_all(parent),
+            None => Ok(()),
+        }
+        .and_then(|()| translate_file(path, &target, filter, chain, rng));
+
+        match result {
+            Ok(()) => inform(
+                quiet,
+                format_args!("Translated `{}` to `{}`", path.display(), target.display()),
+            ),
+            Err(e) if e.kind() == ErrorKind::InvalidData => inform(
+                quiet,
+                format_args!("Skipped `{}`, it is not a text file", path.display()),
+            ),
+            Err(e) => {
+                eprintln!("Could not translate `{}`: {}", path.display(), e);
+                failed = true;
+            }
+        }
+    }
+
+    Ok(failed)
+}
+
+/// Translates `input` through `chain` one line at a time, writing each line to
+/// `output` as soon as it is translated. The output is the same as translating
+/// all of `input` at once, except for phrases and sentences that span lines.
+/// Like that, leading blank lines and trailing whitespace are dropped; the
+/// trailing whitespace is returned instead of written.
+fn translate_stream(
+    mut input: impl BufRead,
+    output: &mut impl Write,
+    chain: &[CompiledLanguage],
+    rng: &mut dyn Rng,
+) -> std::io::Result<String> {
+    let mut started = false;
+    // Whitespace that is only written once more text follows.
+    let mut pending = String::new();
+    let mut line = String::new();
+
+    loop {
+        line.clear();
+        if input.read_line(&mut line)? == 0 {
+            break Ok(pending);
+        }
+
+        if line.trim().is_empty() {
+            pending.push_str(&line);
+            continue;
+        }
+
+        if started {
+            output.write_all(pending.as_bytes())?;
+        }
+
+        let content = line.trim_end();
+        output.write_all(translate_chain(content, chain, rng).as_bytes())?;
+        pending.clear();
+        pending.push_str(&line[content.len()..]);
+        started = true;
+    }
+}
+
+/// Translates `text` through `chain` and writes every substitution the last
+/// language makes to `out`, followed by the translation. Positions count chars
+/// in the text that language was given.
+fn dry_run(
+    text: &str,
+    chain: &[CompiledLanguage],
+    rng: &mut dyn Rng,
+    out: &mut impl Write,
+) -> std::io::Result<()> {
+    let Some((last, stages)) = chain.split_last() else {
+        return writeln!(out, "{}", trim_blank_lines(text));
+    };
+
+    let (input, made) = translate_chain_counted(text, stages, rng);
+    let (translated, substitutions) = last.translate_matches_after(&input, made, rng);
+    let input = trim_blank_lines(&input);
+
+    for substitution in substitutions {
+        writeln!(
+            out,
+            "\"{}\" -> \"{}\" at position {}",
+            &input[substitution.source.clone()],
+            &translated[substitution.target],
+            input[..substitution.source.start].chars().count()
+        )?;
+    }
+
+    writeln!(out, "{translated}")
+}
+
+/// Translates `text` through all but the last language of `chain` and writes
+/// a line to `out` for every word of the result, telling why the last
+/// language does or doesn't translate it.
+fn explain(
+    text: &str,
+    chain: &[CompiledLanguage],
+    rng: &mut dyn Rng,
+    out: &mut impl Write,
+) -> std::io::Result<()> {
+    let Some((last, stages)) = chain.split_last() else {
+        return Ok(());
+    };
+
+    let (input, made) = translate_chain_counted(text, stages, rng);
+    let case = if last.match_case_insensitive {
+        "ignoring case"
+    } else {
+        "matching case"
+    };
+
+    for (word, explanation) in last.explain_after(&input, made) {
+        match explanation {
+            Explanation::Matched(key) => writeln!(
+                out,
+                "\"{word}\": translated by key \"{key}\", {case}, in {} mode",
+                last.lower_mode
+            )?,
+            Explanation::Protected => writeln!(out, "\"{word}\": not translated, it is protected")?,
+            Explanation::Outranked(key) => writeln!(
+                out,
+                "\"{word}\": not translated, key \"{key}\" matches it but an overlapping match, protected text or the maximum number of replacements came first"
+            )?,
+            Explanation::Guarded(key) => writeln!(
+                out,
+                "\"{word}\": not translated, key \"{key}\" matches it but its before or after guard doesn't hold here"
+            )?,
+            Explanation::NoKey => writeln!(out, "\"{word}\": not translated, no key matches it")?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Appends the line breaks that end `original` to `translated`, since
+/// translating trims them.
+fn with_trailing_newlines(original: &str, translated: &str) -> String {
+    let trailing = &original[original.trim_end_matches(['\r', '\n']).len()..];
+    format!("{translated}{trailing}")
+}
+
+/// Puts the blank lines and whitespace that translating trims from `original`
+/// back around `translated`.
+fn untrimmed(original: &str, translated: &str) -> String {
+    let kept = trimmed_range(original);
+    format!(
+        "{}{translated}{}",
+        &original[..kept.start],
+        &original[kept.end..]
+    )
+}
+
+/// Writes the completion script for `shell` to `out`.
+fn print_completions(shell: Shell, out: &mut impl Write) {
+    clap_complete::generate(shell, &mut Cli::command(), "tranclator", out);
+}
+
+/// Writes [`STARTER_CONFIG`] to `path`. Fails with [`ErrorKind::AlreadyExists`]
+/// if the file exists and `force` is not set.
+fn init_config(path: &str, force: bool) -> std::io::Result<()> {
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .create_new(!force)
+        .truncate(true)
+        .open(path)?;
+
+    file.write_all(STARTER_CONFIG.as_bytes())
+}
+
+/// Decides whether translations are copied to the clipboard. `--no-clipboard`
+/// takes precedence over the language setting, which takes precedence over the
+/// global setting. Copying is enabled when none of them say otherwise.
+fn should_copy(no_clipboard: bool, language: &Language, global: Option<&Global>) -> bool {
+    !no_clipboard
+        && language
+            .copy_to_clipboard
+            .or_else(|| global?.copy_to_clipboard)
+            .unwrap_or(true)
+}
+
+fn list_languages(config: &Config, out: &mut impl Write) -> std::io::Result<()> {
+    for language in &config.languages {
+        writeln!(
+            out,
+            "{} ({}, {} entries)",
+            language.name,
+            language.lower_mode,
+            language.dict.len()
+        )?;
+    }
+
+    Ok(())
+}
+
+/// Counts how often each dictionary key of `language` matches in the files
+/// under `dir` and writes the counts to `out`, most used first. Keys that
+/// never match come last with a count of 0, in dictionary order. Files that
+/// are not text are skipped. Returns whether any file could not be read.
+fn report(dir: &Path, language: &CompiledLanguage, out: &mut impl Write) -> std::io::Result<bool> {
+    let mut counts: IndexMap<&str, usize> =
+        language.dict.keys().map(|key| (key.as_str(), 0)).collect();
+    let mut failed = false;
+
+    for entry in WalkDir::new(dir).sort_by_file_name() {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                eprintln!("Could not read `{}`: {}", dir.display(), e);
+                failed = true;
+                continue;
+            }
+        };
+
+        if !entry.file_type().is_file() {
+            continue;
+        }
+
+        match std::fs::read_to_string(entry.path()) {
+            Ok(text) if !text.contains('\0') => {
+                for key in language.matched_keys(&text) {
+                    *counts.entry(key).or_default() += 1;
+                }
+            }
+            Ok(_) => {}
+            Err(e) if e.kind() == ErrorKind::InvalidData => {}
+            Err(e) => {
+                eprintln!("Could not read `{}`: {}", entry.path().display(), e);
+                failed = true;
+            }
+        }
+    }
+
+    counts.sort_by(|_, a, _, b| b.cmp(a));
+    let width = counts
+        .values()
+        .map(|count| count.to_string().len())
+        .max()
+        .unwrap_or_default();
+
+    for (key, count) in counts {
+        writeln!(out, "{count:>width$}  {key}")?;
+    }
+
+    Ok(failed)
+}
+
+/// Translates every alternative translation of every key of `language` back
+/// with [`reverse_language`] and writes a line to `out` for each one that
+/// doesn't come back as its key, compared the way keys match. Pattern keys are
+/// skipped. Returns whether every entry came back.
+fn verify_roundtrip(
+    language: &Language,
+    rng: &mut dyn Rng,
+    out: &mut impl Write,
+) -> std::io::Result<bool> {
+    let reverse = CompiledLanguage::new(reverse_language(language));
+    let fold = |s: &str| {
+        if language.match_case_insensitive {
+            s.to_lowercase()
+        } else {
+            s.to_string()
+        }
+    };
+    let mut lossless = true;
+
+    for (key, translation) in &language.dict {
+        if key.starts_with(PATTERN_PREFIX) {
+            continue;
+        }
+
+        let (word, _, _) = strip_anchors(key);
+
+        for alternative in translation.alternatives() {
+            let (back, _) = reverse.translate_matches(alternative, rng);
+
+            if fold(&back) != fold(word) {
+                writeln!(out, "\"{key}\" -> \"{alternative}\" -> \"{back}\"")?;
+                lossless = false;
+            }
+        }
+    }
+
+    if lossless {
+        writeln!(out, "Every entry translates back to its key")?;
+    }
+
+    Ok(lossless)
+}
+
+fn show_dict(language: &Language, out: &mut impl Write) -> std::io::Result<()> {
+    if language.dict.is_empty() {
+        return writeln!(out, "Language {} has an empty dictionary", language.name);
+    }
+
+    let width = language
+        .dict
+        .keys()
+        .map(|word| word.chars().count())
+        .max()
+        .unwrap_or_default();
+
+    for (word, translation) in &language.dict {
+        match translation.note() {
+            Some(note) => writeln!(out, "{word:<width$} -> {translation}  # {note}")?,
+            None => writeln!(out, "{word:<width$} -> {translation}")?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns a random number generator seeded with `seed`, or from system
+/// entropy if there is no seed, so that runs with the same seed pick the same
+/// alternative translations.
+fn seeded_rng(seed: Option<u64>) -> StdRng {
+    match seed {
+        Some(seed) => StdRng::seed_from_u64(seed),
+        None => rand::make_rng(),
+    }
+}
+
+/// A one-shot translation as printed by `--format json`.
+#[derive(Serialize, Debug)]
+struct Report<'a> {
+    /// The input, without the blank lines around it that translating drops.
+    original: &'a str,
+    translated: &'a str,
+    language: &'a str,
+    substitutions: &'a [Substitution],
+}
+
+/// Wraps every span of `text` in ANSI escape codes that color it green.
+fn highlight(text: &str, spans: &[Range<usize>]) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut last = 0;
+
+    for span in spans {
+        result.push_str(&text[last..span.start]);
+        result.push_str("\x1b[32m");
+        result.push_str(&text[span.clone()]);
+        result.push_str("\x1b[0m");
+        last = span.end;
+    }
+
+    result.push_str(&text[last..]);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::RngExt;
+    use rand::seq::IndexedRandom;
+    use rustyline::history::History;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use tranclator::{Translation, translate_matches};
+
+    /// Records every text written to it and hands back the last one on read.
+    #[derive(Default, Clone)]
+    struct MockClipboard {
+        writes: Rc<RefCell<Vec<String>>>,
+    }
+
+    impl ClipboardSink for MockClipboard {
+        fn set_text(&mut self, text: &str) -> Result<(), Box<dyn std::error::Error>> {
+            self.writes.borrow_mut().push(text.to_string());
+            Ok(())
+        }
+
+        fn get_text(&mut self) -> Result<String, Box<dyn std::error::Error>> {
+            Ok(self
+                .writes
+                .borrow()
+                .last()
+                .cloned()
+                .ok_or("clipboard is empty")?)
+        }
+    }
+
+    impl Repl {
+        /// Returns a REPL for `languages` that translates with the first one,
+        /// without a clipboard or config and with `quit` as its quit word.
+        fn for_test(languages: Vec<Language>) -> Self {
+            Repl {
+                config_paths: Vec::new(),
+                chain: compile_chain(languages[..1].to_vec(), None),
+                languages,
+                rules: Vec::new(),
+                capitalization: None,
+                reverse: false,
+                cb: None,
+                previous_clipboard: None,
+                multiline: None,
+                quit_words: QuitWords::new(["quit".to_string()], false),
+                prompt: ">>> ".to_string(),
+                color: false,
+                quiet: false,
+                trim: true,
+                max_replacements: None,
+                learning: None,
+                rng: StdRng::seed_from_u64(0),
+            }
+        }
+    }
+
+    fn translate(text: &str, language: &Language) -> String {
+        translate_with_rng(text, language, &mut rand::rng())
+    }
+
+    fn translate_with_rng(text: &str, language: &Language, rng: &mut dyn Rng) -> String {
+        translate_matches(text, language, rng).0
+    }
+
+    /// Translates `text` like [`translate_with_rng`], also returning the byte
+    /// ranges of the translated string that were substituted from the
+    /// dictionary.
+    fn translate_spans(
+        text: &str,
+        language: &Language,
+        rng: &mut dyn Rng,
+    ) -> (String, Vec<Range<usize>>) {
+        translate_chain_spans(text, &[CompiledLanguage::new(language.clone())], rng)
+    }
+
+    fn dict(entries: &[(&str, &str)]) -> IndexMap<String, Translation> {
+        entries
+            .iter()
+            .map(|&(word, translation)| (word.to_string(), translation.into()))
+            .collect()
+    }
+
+    #[test]
+    fn test_list_languages() {
+        let config: Config = toml::from_str(
+            r#"
+            [[language]]
+            name = "first"
+            lower-mode = "lower"
+            dict = { a = "b", c = "d" }
+
+            [[language]]
+            name = "second"
+            lower-mode = "preserve"
+            dict = {}
+            "#,
+        )
+        .unwrap();
+
+        let mut out = Vec::new();
+        list_languages(&config, &mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "first (lower, 2 entries)\nsecond (preserve, 0 entries)\n"
+        );
+    }
+
+    #[test]
+    fn test_show_dict() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("hello", "hola"), ("cat", "gato")]),
+            ..Default::default()
+        };
+
+        let mut out = Vec::new();
+        show_dict(&language, &mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "hello -> hola\ncat   -> gato\n"
+        );
+    }
+
+    #[test]
+    fn test_entry_notes() {
+        let config: Config = toml::from_str(
+            r#"
+            [[language]]
+            name = "test"
+            lower-mode = "lower"
+
+            [language.dict]
+            "hello" = { translation = "hola", note = "informal" }
+            "cat" = "gato"
+            "#,
+        )
+        .unwrap();
+        let language = &config.languages[0];
+
+        assert_eq!(language.dict["hello"].note(), Some("informal"));
+        assert_eq!(translate("hello cat", language), "hola gato");
+
+        let mut out = Vec::new();
+        show_dict(language, &mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "hello -> hola  # informal\ncat   -> gato\n"
+        );
+    }
+
+    #[test]
+    fn test_show_empty_dict() {
+        let language = Language {
+            name: "test".to_string(),
+            ..Default::default()
+        };
+
+        let mut out = Vec::new();
+        show_dict(&language, &mut out).unwrap();
+
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "Language test has an empty dictionary\n"
+        );
+    }
+
+    #[test]
+    fn test_init_config() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tranclator.toml");
+        let path = path.to_str().unwrap();
+
+        init_config(path, false).unwrap();
+
+        let config = parse_config(path, &std::fs::read_to_string(path).unwrap()).unwrap();
+        let global = config.global.unwrap();
+        let language = &config.languages[0];
+        assert_eq!(
+            global.default_language.as_deref(),
+            Some(language.name.as_str())
+        );
+        assert_eq!(translate("Hello friend", language), "Ahoy matey");
+    }
+
+    #[test]
+    fn test_init_config_refuses_overwrite() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tranclator.toml");
+        std::fs::write(&path, "# mine").unwrap();
+        let path = path.to_str().unwrap();
+
+        let err = init_config(path, false).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::AlreadyExists);
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "# mine");
+
+        init_config(path, true).unwrap();
+        assert_eq!(std::fs::read_to_string(path).unwrap(), STARTER_CONFIG);
+    }
+
+    #[test]
+    fn test_check_valid_config() {
+        let config: Config = toml::from_str(STARTER_CONFIG).unwrap();
+        assert!(check_config(&config).is_empty());
+    }
+
+    #[test]
+    fn test_encode() {
+        let latin1 = parse_encoding("latin1").unwrap();
+
+        assert_eq!(encode("café", latin1).unwrap(), b"caf\xe9");
+        assert_eq!(
+            encode("café", encoding_rs::UTF_8).unwrap(),
+            "café".as_bytes()
+        );
+        assert_eq!(
+            encode("caf\u{e9} \u{65e5}", latin1).unwrap_err(),
+            "`\u{65e5}` can't be represented in windows-1252"
+        );
+        assert!(parse_encoding("utf-16").is_err());
+        assert!(parse_encoding("klingon").is_err());
+    }
+
+    #[test]
+    fn test_should_copy_precedence() {
+        let enabled = Global {
+            copy_to_clipboard: Some(true),
+            ..Default::default()
+        };
+        let disabled = Global {
+            copy_to_clipboard: Some(false),
+            ..Default::default()
+        };
+        let language = |copy_to_clipboard| Language {
+            copy_to_clipboard,
+            ..Default::default()
+        };
+
+        assert!(should_copy(false, &language(None), None));
+        assert!(should_copy(
+            false,
+            &language(None),
+            Some(&Global::default())
+        ));
+        assert!(should_copy(false, &language(None), Some(&enabled)));
+        assert!(!should_copy(false, &language(None), Some(&disabled)));
+        assert!(should_copy(false, &language(Some(true)), Some(&disabled)));
+        assert!(!should_copy(false, &language(Some(false)), Some(&enabled)));
+        assert!(!should_copy(true, &language(Some(true)), Some(&enabled)));
+        assert!(!should_copy(true, &language(None), None));
+    }
+
+    #[test]
+    fn test_repl_copies_to_clipboard() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("hello", "hola")]),
+            ..Default::default()
+        };
+        let clipboard = MockClipboard::default();
+        let mut output = Vec::new();
+        let mut errors = Vec::new();
+
+        Repl {
+            cb: Some(Box::new(clipboard.clone())),
+            ..Repl::for_test(vec![language])
+        }
+        .run(
+            &mut "hello there\nquit\n".as_bytes(),
+            &mut output,
+            &mut errors,
+        )
+        .unwrap();
+
+        assert_eq!(*clipboard.writes.borrow(), ["hola there"]);
+        assert!(
+            String::from_utf8(output)
+                .unwrap()
+                .contains(">>> hola there\n")
+        );
+    }
+
+    #[test]
+    fn test_repl_undo_restores_clipboard() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("hello", "hola")]),
+            ..Default::default()
+        };
+        let clipboard = MockClipboard::default();
+        let mut output = Vec::new();
+        let mut errors = Vec::new();
+        let mut repl = Repl {
+            cb: Some(Box::new(clipboard.clone())),
+            ..Repl::for_test(vec![language])
+        };
+
+        repl.handle_line("hello", &mut output, &mut errors).unwrap();
+        repl.handle_line(":undo", &mut output, &mut errors).unwrap();
+
+        clipboard.writes.borrow_mut().push("mine".to_string());
+        repl.handle_line("hello there", &mut output, &mut errors)
+            .unwrap();
+        repl.handle_line(":undo", &mut output, &mut errors).unwrap();
+        repl.handle_line(":undo", &mut output, &mut errors).unwrap();
+
+        assert_eq!(
+            *clipboard.writes.borrow(),
+            ["hola", "mine", "hola there", "mine"]
+        );
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "hola\nhola there\nRestored the clipboard\n"
+        );
+        assert_eq!(
+            String::from_utf8(errors).unwrap(),
+            "Could not restore the clipboard, it couldn't be read before the last translation\nNothing to undo\n"
+        );
+    }
+
+    #[test]
+    fn test_clipboard_wait_argument() {
+        let args = Cli::try_parse_from(["tranclator"]).unwrap();
+        assert_eq!(args.clipboard_wait, 100);
+
+        let args = Cli::try_parse_from(["tranclator", "--clipboard-wait", "0"]).unwrap();
+        assert_eq!(args.clipboard_wait, 0);
+
+        let mut clipboard = MockClipboard::default();
+        copy_and_wait(
+            &mut clipboard,
+            "hola",
+            std::time::Duration::from_millis(args.clipboard_wait),
+        )
+        .unwrap();
+        assert_eq!(*clipboard.writes.borrow(), ["hola"]);
+    }
+
+    #[test]
+    fn test_clipboard_target_argument() {
+        let args = Cli::try_parse_from(["tranclator"]).unwrap();
+        assert_eq!(args.clipboard_target, ClipboardTarget::Clipboard);
+
+        for (value, target) in [
+            ("clipboard", ClipboardTarget::Clipboard),
+            ("primary", ClipboardTarget::Primary),
+            ("both", ClipboardTarget::Both),
+        ] {
+            let args = Cli::try_parse_from(["tranclator", "--clipboard-target", value]).unwrap();
+            assert_eq!(args.clipboard_target, target);
+        }
+
+        assert!(Cli::try_parse_from(["tranclator", "--clipboard-target", "secondary"]).is_err());
+    }
+
+    #[test]
+    fn test_from_clipboard() {
+        let args = Cli::try_parse_from(["tranclator", "--from-clipboard", "-l", "test"]).unwrap();
+        assert!(args.from_clipboard);
+        assert!(Cli::try_parse_from(["tranclator", "--from-clipboard", "--repl"]).is_err());
+        assert!(
+            Cli::try_parse_from(["tranclator", "--from-clipboard", "--text", "hello"]).is_err()
+        );
+
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("hello", "hola")]),
+            ..Default::default()
+        };
+        let mut clipboard = MockClipboard::default();
+        clipboard.set_text("Hello there").unwrap();
+
+        let translated = translate(&clipboard.get_text().unwrap(), &language);
+        copy_and_wait(&mut clipboard, &translated, std::time::Duration::ZERO).unwrap();
+
+        assert_eq!(*clipboard.writes.borrow(), ["Hello there", "hola there"]);
+    }
+
+    #[test]
+    fn test_watcher() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("hello", "hola"), ("hola", "hallo")]),
+            ..Default::default()
+        };
+        let mut clipboard = MockClipboard::default();
+        let mut watcher = Watcher { last_seen: None };
+
+        assert_eq!(
+            watcher
+                .poll(
+                    &mut clipboard,
+                    &[CompiledLanguage::new(language.clone())],
+                    true,
+                    &mut rand::rng()
+                )
+                .unwrap(),
+            None
+        );
+
+        clipboard.set_text("hello").unwrap();
+        assert_eq!(
+            watcher
+                .poll(
+                    &mut clipboard,
+                    &[CompiledLanguage::new(language.clone())],
+                    true,
+                    &mut rand::rng()
+                )
+                .unwrap(),
+            Some("hola".to_string())
+        );
+        // The written translation must not be translated again.
+        assert_eq!(
+            watcher
+                .poll(
+                    &mut clipboard,
+                    &[CompiledLanguage::new(language.clone())],
+                    true,
+                    &mut rand::rng()
+                )
+                .unwrap(),
+            None
+        );
+
+        clipboard.set_text("hello world").unwrap();
+        assert_eq!(
+            watcher
+                .poll(
+                    &mut clipboard,
+                    &[CompiledLanguage::new(language.clone())],
+                    true,
+                    &mut rand::rng()
+                )
+                .unwrap(),
+            Some("hola world".to_string())
+        );
+
+        assert_eq!(
+            *clipboard.writes.borrow(),
+            ["hello", "hola", "hello world", "hola world"]
+        );
+    }
+
+    #[test]
+    fn test_watcher_without_write_back() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("hello", "hola")]),
+            ..Default::default()
+        };
+        let mut clipboard = MockClipboard::default();
+        clipboard.set_text("hello").unwrap();
+        let mut watcher = Watcher { last_seen: None };
+
+        assert_eq!(
+            watcher
+                .poll(
+                    &mut clipboard,
+                    &[CompiledLanguage::new(language.clone())],
+                    false,
+                    &mut rand::rng()
+                )
+                .unwrap(),
+            Some("hola".to_string())
+        );
+        assert_eq!(
+            watcher
+                .poll(
+                    &mut clipboard,
+                    &[CompiledLanguage::new(language.clone())],
+                    false,
+                    &mut rand::rng()
+                )
+                .unwrap(),
+            None
+        );
+        assert_eq!(*clipboard.writes.borrow(), ["hello"]);
+    }
+
+    #[test]
+    fn test_repl_switch_language() {
+        let spanish = Language {
+            name: "spanish".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("hello", "hola")]),
+            ..Default::default()
+        };
+        let dutch = Language {
+            name: "dutch".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("hello", "hallo")]),
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        let mut errors = Vec::new();
+
+        Repl::for_test(vec![spanish, dutch])
+            .run(
+                &mut "hello\n:lang dutch\nhello\n:lang french\n:lang duthc\nhello\nquit\n"
+                    .as_bytes(),
+                &mut output,
+                &mut errors,
+            )
+            .unwrap();
+
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().skip(4).collect();
+        assert_eq!(
+            lines,
+            [
+                ">>> hola",
+                ">>> Switched to dutch",
+                ">>> hallo",
+                ">>> >>> >>> hallo",
+                ">>> ",
+            ]
+        );
+        assert_eq!(
+            String::from_utf8(errors).unwrap(),
+            "Language french not found\nLanguage duthc not found, did you mean `dutch`?\n"
+        );
+    }
+
+    #[test]
+    fn test_history_file_argument() {
+        let args = Cli::try_parse_from(["tranclator", "--repl"]).unwrap();
+        assert_eq!(history_path(args.history_file), None);
+
+        let args =
+            Cli::try_parse_from(["tranclator", "--repl", "--history-file", "history.txt"]).unwrap();
+        assert_eq!(
+            history_path(args.history_file),
+            Some(PathBuf::from("history.txt"))
+        );
+
+        let args = Cli::try_parse_from(["tranclator", "--repl", "--history-file"]).unwrap();
+        if let Some(dirs) = ProjectDirs::from("", "", "tranclator") {
+            assert_eq!(
+                history_path(args.history_file),
+                Some(dirs.cache_dir().join("history"))
+            );
+        }
+    }
+
+    #[test]
+    fn test_open_editor_loads_history() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("history");
+
+        let mut editor = open_editor(Some(&path)).unwrap();
+        assert!(editor.history().is_empty());
+        editor.add_history_entry("hello").unwrap();
+        editor.add_history_entry("world").unwrap();
+        editor.save_history(&path).unwrap();
+
+        let editor = open_editor(Some(&path)).unwrap();
+        let entries: Vec<&String> = editor.history().iter().collect();
+        assert_eq!(entries, ["hello", "world"]);
+    }
+
+    #[test]
+    fn test_complete_word() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("hello", "hola"), ("Help", "ayuda"), ("world", "mundo")]),
+            ..Default::default()
+        };
+        let completer = DictCompleter::new(&language);
+
+        assert_eq!(
+            completer.complete_word("say HEL", 7),
+            (4, vec!["hello".to_string(), "Help".to_string()])
+        );
+        assert_eq!(
+            completer.complete_word("hello wo", 8),
+            (6, vec!["world".to_string()])
+        );
+        assert_eq!(
+            completer.complete_word("hel wo", 3),
+            (0, vec!["hello".to_string(), "Help".to_string()])
+        );
+        assert_eq!(completer.complete_word("hello ", 6), (6, Vec::new()));
+        assert_eq!(completer.complete_word("xyz", 3), (0, Vec::new()));
+    }
+
+    #[test]
+    fn test_translate_spans() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("hello", "hola"), ("world", "mundo")]),
+            ..Default::default()
+        };
+
+        let (translated, spans) = translate_spans("hello big world", &language, &mut rand::rng());
+        assert_eq!(translated, "hola big mundo");
+        assert_eq!(spans, [0..4, 9..14]);
+        assert_eq!(&translated[spans[1].clone()], "mundo");
+
+        assert_eq!(
+            highlight(&translated, &spans),
+            "\x1b[32mhola\x1b[0m big \x1b[32mmundo\x1b[0m"
+        );
+    }
+
+    #[test]
+    fn test_seed() {
+        let language = Language {
+            lower_mode: CapitalizationMode::Lower,
+            dict: [(
+                "a".to_string(),
+                Translation::Choice(('a'..='z').map(String::from).collect()),
+            )]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        };
+        let text = "a".repeat(32);
+
+        let first = translate_with_rng(&text, &language, &mut seeded_rng(Some(7)));
+        let second = translate_with_rng(&text, &language, &mut seeded_rng(Some(7)));
+        let other = translate_with_rng(&text, &language, &mut seeded_rng(Some(8)));
+
+        assert_eq!(first, second);
+        assert_ne!(first, other);
+    }
+
+    #[test]
+    fn test_completions_for_every_shell() {
+        for shell in [Shell::Bash, Shell::Zsh, Shell::Fish, Shell::PowerShell] {
+            let mut out = Vec::new();
+            print_completions(shell, &mut out);
+
+            let script = String::from_utf8(out).unwrap();
+            assert!(script.contains("language"), "{shell}");
+        }
+    }
+
+    #[test]
+    fn test_config_paths_in() {
+        let args = |args: &[&str]| -> Vec<String> { args.iter().map(|a| a.to_string()).collect() };
+
+        assert!(config_paths_in(&args(&["tranclator", "--", "tranclator", "-l", ""])).is_empty());
+        assert_eq!(
+            config_paths_in(&args(&[
+                "tranclator",
+                "--config-path",
+                "a.toml",
+                "--config-path=b.yaml",
+                "-l"
+            ])),
+            ["a.toml", "b.yaml"]
+        );
+    }
+
+    #[test]
+    fn test_resolve_config_paths() {
+        let cli = ["a.toml".to_string(), "b.toml".to_string()];
+        let env = || Some("env.toml".to_string());
+        let search = [PathBuf::from("missing.toml")];
+
+        assert_eq!(resolve_config_paths(&cli, env(), &search), cli);
+        assert_eq!(resolve_config_paths(&cli, None, &search), cli);
+        assert_eq!(resolve_config_paths(&[], env(), &search), ["env.toml"]);
+        assert_eq!(resolve_config_paths(&[], None, &search), ["missing.toml"]);
+        assert_eq!(
+            resolve_config_paths(&[], Some(String::new()), &search),
+            ["missing.toml"]
+        );
+    }
+
+    #[test]
+    fn test_resolve_config_paths_searches_home() {
+        let home = tempfile::tempdir().unwrap();
+        let local = home.path().join("work").join("tranclator.toml");
+        let user = home
+            .path()
+            .join(".config")
+            .join("tranclator")
+            .join("config.toml");
+        let search = [local.clone(), user.clone()];
+        let resolve = || resolve_config_paths(&[], None, &search);
+
+        assert_eq!(resolve(), [local.to_str().unwrap()]);
+
+        std::fs::create_dir_all(user.parent().unwrap()).unwrap();
+        std::fs::write(&user, "").unwrap();
+        assert_eq!(resolve(), [user.to_str().unwrap()]);
+
+        std::fs::create_dir_all(local.parent().unwrap()).unwrap();
+        std::fs::write(&local, "").unwrap();
+        assert_eq!(resolve(), [local.to_str().unwrap()]);
+    }
+
+    #[test]
+    fn test_batch_output_path() {
+        let path = |p: &str| batch_output_path(Path::new(p));
+
+        assert_eq!(path("docs/a.txt"), PathBuf::from("docs/a.out"));
+        assert_eq!(path("b"), PathBuf::from("b.out"));
+        assert_eq!(path("c.out"), PathBuf::from("c.out.out"));
+    }
+
+    #[test]
+    fn test_stream_matches_batch_on_large_input() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Preserve,
+            dict: {
+                let mut dict = dict(&[("hello", "hola"), ("world", "mundo"), ("a", "e")]);
+                dict.insert(
+                    "cat".to_string(),
+                    Translation::Choice(vec!["gato".to_string(), "poes".to_string()]),
+                );
+                dict
+            },
+            whole_word: true,
+            ..Default::default()
+        };
+        let chain = [CompiledLanguage::new(language)];
+
+        let words = ["Hello", "world", "CAT", "a", "dog", "cat", "HELLO", "x"];
+        let mut rng = StdRng::seed_from_u64(1);
+        let mut text = String::from("\n  \n");
+        while text.len() < 2 << 20 {
+            let line: Vec<&str> = (0..rng.random_range(0..12))
+                .map(|_| *words.choose(&mut rng).unwrap())
+                .collect();
+            text.push_str(&line.join(" "));
+            text.push_str(if rng.random_bool(0.1) { "  \n" } else { "\n" });
+        }
+        text.push_str("\n\n");
+
+        let batch = translate_chain(&text, &chain, &mut StdRng::seed_from_u64(2));
+
+        let mut streamed = Vec::new();
+        let rest = translate_stream(
+            text.as_bytes(),
+            &mut streamed,
+            &chain,
+            &mut StdRng::seed_from_u64(2),
+        )
+        .unwrap();
+
+        assert_eq!(String::from_utf8(streamed).unwrap(), batch);
+        assert!(rest.ends_with("\n\n\n"));
+    }
+
+    #[test]
+    fn test_stream_keeps_blank_lines_between_text() {
+        let chain = [CompiledLanguage::new(Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("hello", "hola")]),
+            ..Default::default()
+        })];
+        let text = "\n\n  hello  \n \n\nHello\n\n";
+        let mut streamed = Vec::new();
+
+        let rest =
+            translate_stream(text.as_bytes(), &mut streamed, &chain, &mut rand::rng()).unwrap();
+
+        assert_eq!(String::from_utf8(streamed).unwrap(), "  hola  \n \n\nhola");
+        assert_eq!(rest, "\n\n");
+        assert_eq!(
+            translate_chain(text, &chain, &mut rand::rng()),
+            "  hola  \n \n\nhola"
+        );
+    }
+
+    #[test]
+    fn test_translate_lines_keeps_unmatched_lines() {
+        let chain = [CompiledLanguage::new(Language {
+            dict: dict(&[("hello", "hola")]),
+            ..Default::default()
+        })];
+        let filter = Regex::new("^# ").unwrap();
+
+        assert_eq!(
+            translate_lines(
+                "\nhello\r\n# hello  \n#\n# \n# Hello\n\n",
+                &filter,
+                &chain,
+                &mut rand::rng()
+            ),
+            "\nhello\r\n# hola  \n#\n# \n# Hola"
+        );
+    }
+
+    #[test]
+    fn test_untrimmed_keeps_surrounding_whitespace() {
+        let chain = [CompiledLanguage::new(Language {
+            dict: dict(&[("hello", "hola")]),
+            ..Default::default()
+        })];
+        let text = "\n \n  hello there  \n\t\n";
+        let translated = translate_chain(text, &chain, &mut rand::rng());
+
+        assert_eq!(translated, "  hola there");
+        assert_eq!(untrimmed(text, &translated), "\n \n  hola there  \n\t\n");
+        assert_eq!(untrimmed(" \n ", ""), " \n ");
+    }
+
+    #[test]
+    fn test_dry_run_lists_substitutions() {
+        let chain = compile_chain(
+            vec![
+                Language {
+                    name: "first".to_string(),
+                    dict: dict(&[("colour", "color")]),
+                    ..Default::default()
+                },
+                Language {
+                    name: "second".to_string(),
+                    lower_mode: CapitalizationMode::Preserve,
+                    dict: dict(&[("cat", "gato"), ("color", "kleur"), ("héllo", "hola")]),
+                    ..Default::default()
+                },
+            ],
+            None,
+        );
+        let text = "\nHéllo, the Cat has a colour\n";
+        let mut output = Vec::new();
+
+        dry_run(text, &chain, &mut rand::rng(), &mut output).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            concat!(
+                "\"Héllo\" -> \"Hola\" at position 0\n",
+                "\"Cat\" -> \"Gato\" at position 11\n",
+                "\"color\" -> \"kleur\" at position 21\n",
+                "Hola, the Gato has a kleur\n",
+            )
+        );
+        assert_eq!(
+            translate_chain(text, &chain, &mut rand::rng()),
+            "Hola, the Gato has a kleur"
+        );
+    }
+
+    #[test]
+    fn test_parse_rule() {
+        assert_eq!(
+            parse_rule("foo=bar"),
+            Ok(("foo".to_string(), "bar".to_string()))
+        );
+        assert_eq!(
+            parse_rule("a=b=c"),
+            Ok(("a".to_string(), "b=c".to_string()))
+        );
+        assert_eq!(parse_rule("foo="), Ok(("foo".to_string(), String::new())));
+        assert!(parse_rule("foo").is_err());
+        assert!(parse_rule("=bar").is_err());
+    }
+
+    #[test]
+    fn test_cli() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_repl_ends_at_end_of_input() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("hello", "hola")]),
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        let mut errors = Vec::new();
+
+        Repl::for_test(vec![language])
+            .run(&mut "hello\nhello".as_bytes(), &mut output, &mut errors)
+            .unwrap();
+
+        assert!(
+            String::from_utf8(output)
+                .unwrap()
+                .ends_with(">>> hola\n>>> hola\n>>> \nGoodbye!\n")
+        );
+    }
+
+    #[test]
+    fn test_default_quit_words() {
+        assert_eq!(
+            quit_words(None).words,
+            HashSet::from(DEFAULT_QUIT_KEYWORDS.map(String::from))
+        );
+        assert_eq!(quit_words(Some(&Global::default())), quit_words(None));
+
+        let global = Global {
+            quit_keywords: Some(vec!["bye".to_string()]),
+            ..Default::default()
+        };
+        assert_eq!(
+            quit_words(Some(&global)).words,
+            HashSet::from(["bye".to_string()])
+        );
+
+        for word in DEFAULT_QUIT_KEYWORDS {
+            let language = Language {
+                name: "test".to_string(),
+                ..Default::default()
+            };
+            let mut output = Vec::new();
+            let mut errors = Vec::new();
+
+            Repl {
+                quit_words: quit_words(None),
+                ..Repl::for_test(vec![language])
+            }
+            .run(
+                &mut format!("{word}\nhello\n").as_bytes(),
+                &mut output,
+                &mut errors,
+            )
+            .unwrap();
+
+            let output = String::from_utf8(output).unwrap();
+            assert!(output.contains("Type any of \":q\", \"exit\", \"quit\" to exit"));
+            assert!(output.ends_with(">>> "), "{word}");
+        }
+    }
+
+    #[test]
+    fn test_repl_multiline_input() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("hello", "hola"), ("world", "mundo")]),
+            ..Default::default()
+        };
+        let clipboard = MockClipboard::default();
+        let mut output = Vec::new();
+        let mut errors = Vec::new();
+
+        Repl {
+            cb: Some(Box::new(clipboard.clone())),
+            quiet: true,
+            ..Repl::for_test(vec![language])
+        }
+        .run(
+            &mut "hello\\\nworld\n:paste\nhello\n\nquit\n:end\nquit\n".as_bytes(),
+            &mut output,
+            &mut errors,
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            ">>> ... hola\nmundo\n>>> ... ... ... ... hola\n\nquit\n>>> "
+        );
+        assert_eq!(*clipboard.writes.borrow(), ["hola\nmundo", "hola\n\nquit"]);
+    }
+
+    #[test]
+    fn test_explain() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("hello world", "hola mundo"), ("hello", "hola")]),
+            protect: vec!["there".to_string()],
+            ..Default::default()
+        };
+        let chain = compile_chain(vec![language], Some(1));
+        let mut output = Vec::new();
+
+        explain(
+            "Hello World, cat there hello",
+            &chain,
+            &mut StdRng::seed_from_u64(0),
+            &mut output,
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output)
+                .unwrap()
+                .lines()
+                .collect::<Vec<_>>(),
+            [
+                "\"Hello\": translated by key \"hello world\", ignoring case, in lower mode",
+                "\"World\": translated by key \"hello world\", ignoring case, in lower mode",
+                "\"cat\": not translated, no key matches it",
+                "\"there\": not translated, it is protected",
+                "\"hello\": not translated, key \"hello\" matches it but an overlapping match, protected text or the maximum number of replacements came first",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_translate_json() {
+        let chain = compile_chain(
+            vec![Language {
+                name: "test".to_string(),
+                lower_mode: CapitalizationMode::Lower,
+                dict: dict(&[("hello", "hola"), ("world", "mundo"), ("title", "titulo")]),
+                ..Default::default()
+            }],
+            None,
+        );
+        let text = r#"{"title": "hello", "count": 2, "pages": [{"title": "hello world", "tags": ["world", null]}], "meta": {"hello": "world"}}"#;
+        let translate = |paths: &[&str]| -> serde_json::Value {
+            let paths: Vec<String> = paths.iter().map(|p| p.to_string()).collect();
+            let translated =
+                translate_json(text, &paths, &chain, &mut StdRng::seed_from_u64(0)).unwrap();
+            serde_json::from_str(&translated).unwrap()
+        };
+
+        assert_eq!(
+            translate(&[]),
+            serde_json::json!({
+                "title": "hola",
+                "count": 2,
+                "pages": [{"title": "hola mundo", "tags": ["mundo", null]}],
+                "meta": {"hello": "mundo"}
+            })
+        );
+        assert_eq!(
+            translate(&["$.pages.*.title", "meta"]),
+            serde_json::json!({
+                "title": "hello",
+                "count": 2,
+                "pages": [{"title": "hola mundo", "tags": ["world", null]}],
+                "meta": {"hello": "mundo"}
+            })
+        );
+        assert!(translate_json("{", &[], &chain, &mut StdRng::seed_from_u64(0)).is_err());
+        assert!(
+            translate_json(text, &[], &chain, &mut StdRng::seed_from_u64(0))
+                .unwrap()
+                .starts_with("{\n  \"title\": \"hola\",\n  \"count\": 2,")
+        );
+    }
+
+    #[test]
+    fn test_translate_markdown() {
+        let chain = compile_chain(
+            vec![Language {
+                name: "test".to_string(),
+                lower_mode: CapitalizationMode::Preserve,
+                dict: dict(&[
+                    ("hello", "hola"),
+                    ("world", "mundo"),
+                    ("new york", "nueva york"),
+                ]),
+                ..Default::default()
+            }],
+            None,
+        );
+        let text = "# Hello world\n\nSay `hello` to the [world](https://hello.world/world) from new\nyork.\n\n```rust\nlet hello = \"world\";\n```\n\n    hello world\n\n- hello <https://world.example>\n- <b>hello</b> world\n";
+
+        assert_eq!(
+            translate_markdown(text, &chain, &mut StdRng::seed_from_u64(0)),
+            "# Hola mundo\n\nSay `hello` to the [mundo](https://hello.world/world) from nueva york.\n\n```rust\nlet hello = \"world\";\n```\n\n    hello world\n\n- hola <https://world.example>\n- <b>hola</b> mundo"
+        );
+
+        let cased = |lower_mode| {
+            compile_chain(
+                vec![Language {
+                    name: "test".to_string(),
+                    lower_mode,
+                    dict: dict(&[("hello", "hola"), ("world", "mundo")]),
+                    output_prefix: Some("<<".to_string()),
+                    output_suffix: Some(">>".to_string()),
+                    ..Default::default()
+                }],
+                None,
+            )
+        };
+        let text = "hello *world* again. `HELLO` world";
+
+        assert_eq!(
+            translate_markdown(
+                text,
+                &cased(CapitalizationMode::Sentence),
+                &mut StdRng::seed_from_u64(0)
+            ),
+            "<<Hola *mundo* again. `HELLO` mundo>>"
+        );
+        assert_eq!(
+            translate_markdown(
+                text,
+                &cased(CapitalizationMode::Title),
+                &mut StdRng::seed_from_u64(0)
+            ),
+            "<<Hola *Mundo* Again. `HELLO` Mundo>>"
+        );
+    }
+
+    #[test]
+    fn test_report() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("a.txt"), "hello world, hello").unwrap();
+        std::fs::create_dir(dir.path().join("nested")).unwrap();
+        std::fs::write(dir.path().join("nested/b.md"), "Hello cat\nworld").unwrap();
+        std::fs::write(dir.path().join("c.bin"), b"hello\0hello").unwrap();
+        let language = CompiledLanguage::new(Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[
+                ("dog", "perro"),
+                ("world", "mundo"),
+                ("hello", "hola"),
+                ("bird", "pajaro"),
+                ("cat", "gato"),
+            ]),
+            ..Default::default()
+        });
+        let mut output = Vec::new();
+
+        assert!(!report(dir.path(), &language, &mut output).unwrap());
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "3  hello\n2  world\n1  cat\n0  dog\n0  bird\n"
+        );
+    }
+
+    #[test]
+    fn test_verify_roundtrip() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("hello", "hola"), ("hi", "hola"), ("bye", "adios")]),
+            ..Default::default()
+        };
+        let verify = |language: &Language| {
+            let mut output = Vec::new();
+            let lossless =
+                verify_roundtrip(language, &mut StdRng::seed_from_u64(0), &mut output).unwrap();
+            (lossless, String::from_utf8(output).unwrap())
+        };
+
+        assert_eq!(
+            verify(&language),
+            (false, "\"hi\" -> \"hola\" -> \"hello\"\n".to_string())
+        );
+
+        let language = Language {
+            dict: dict(&[("hello", "hola"), ("bye", "adios")]),
+            reverse_dict: Some(IndexMap::from([
+                ("hola".to_string(), "hello".to_string()),
+                ("adios".to_string(), "goodbye".to_string()),
+            ])),
+            ..language
+        };
+        assert_eq!(
+            verify(&language),
+            (false, "\"bye\" -> \"adios\" -> \"goodbye\"\n".to_string())
+        );
+
+        let language = Language {
+            reverse_dict: None,
+            ..language
+        };
+        assert_eq!(
+            verify(&language),
+            (true, "Every entry translates back to its key\n".to_string())
+        );
+    }
+
+    #[test]
+    fn test_render_prompt() {
+        let language = |name: &str, lower_mode| Language {
+            name: name.to_string(),
+            lower_mode,
+            ..Default::default()
+        };
+        let test = compile_chain(vec![language("test", CapitalizationMode::Lower)], None);
+        let chain = compile_chain(
+            vec![
+                language("test", CapitalizationMode::Lower),
+                language("shout", CapitalizationMode::Upper),
+            ],
+            None,
+        );
+
+        assert_eq!(render_prompt(DEFAULT_REPL_PROMPT, &test), "test(lower)>>> ");
+        assert_eq!(
+            render_prompt(DEFAULT_REPL_PROMPT, &chain),
+            "test,shout(upper)>>> "
+        );
+        assert_eq!(
+            render_prompt("[{mode}] {language}> ", &test),
+            "[lower] test> "
+        );
+        assert_eq!(render_prompt(">>> ", &test), ">>> ");
+        assert_eq!(
+            repl_prompt(Some(&Global {
+                repl_prompt: Some("{language}: ".to_string()),
+                ..Default::default()
+            })),
+            "{language}: "
+        );
+        assert_eq!(repl_prompt(None), DEFAULT_REPL_PROMPT);
+    }
+
+    #[test]
+    fn test_quit_words_ignore_case() {
+        let global = Global {
+            quit_keywords: Some(vec!["Bye".to_string(), "quit".to_string()]),
+            ..Default::default()
+        };
+        let words = quit_words(Some(&global));
+
+        assert!(words.matches("bye\n"));
+        assert!(words.matches("  QUIT "));
+        assert!(words.matches("Quit"));
+        assert!(!words.matches("quitting"));
+
+        let strict = quit_words(Some(&Global {
+            case_sensitive_quit: Some(true),
+            ..global
+        }));
+
+        assert!(strict.matches("Bye\n"));
+        assert!(strict.matches("quit"));
+        assert!(!strict.matches("bye"));
+        assert!(!strict.matches("Quit"));
+    }
+
+    #[test]
+    fn test_quiet_repl_only_prints_translations() {
+        let language = Language {
+            name: "test".to_string(),
+            lower_mode: CapitalizationMode::Lower,
+            dict: dict(&[("hello", "hola")]),
+            ..Default::default()
+        };
+        let mut output = Vec::new();
+        let mut errors = Vec::new();
+
+        Repl {
+            quiet: true,
+            ..Repl::for_test(vec![language])
+        }
+        .run(
+            &mut "hello\n:lang test\nhello".as_bytes(),
+            &mut output,
+            &mut errors,
+        )
+        .unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            ">>> hola\n>>> >>> hola\n>>> \n"
+        );
+    }
+
+    #[test]
+    fn test_banner_without_quit_words() {
+        let mut output = Vec::new();
+
+        Repl {
+            quit_words: QuitWords::new([], false),
+            ..Repl::for_test(vec![Language::default()])
+        }
+        .banner(&mut output)
+        .unwrap();
+
+        assert!(
+            String::from_utf8(output)
+                .unwrap()
+                .contains("\nPress Ctrl-D to exit\n")
+        );
+    }
+
+    #[test]
+    fn test_repl_reload() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tranclator.toml");
+        let write = |translation: &str| {
+            std::fs::write(
+                &path,
+                format!(
+                    "[[language]]\nname = \"test\"\nlower-mode = \"lower\"\ndict = {{ hello = \"{translation}\" }}\n"
+                ),
+            )
+            .unwrap();
+        };
+
+        write("hola");
+        let config = load_config(&[path.to_str().unwrap().to_string()]).unwrap();
+        let mut repl = Repl {
+            config_paths: vec![path.to_str().unwrap().to_string()],
+            rules: vec![("bye".to_string(), "adios".to_string())],
+            ..Repl::for_test(config.languages)
+        };
+        let mut output = Vec::new();
+        let mut errors = Vec::new();
+
+        write("hallo");
+        repl.handle_line(":reload", &mut output, &mut errors)
+            .unwrap();
+        repl.handle_line("hello bye", &mut output, &mut errors)
+            .unwrap();
+
+        std::fs::write(&path, "not toml [").unwrap();
+        repl.handle_line(":reload", &mut output, &mut errors)
+            .unwrap();
+        repl.handle_line("hello", &mut output, &mut errors).unwrap();
+
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Reloaded the config\nhallo adios\nhallo\n"
+        );
+        let errors = String::from_utf8(errors).unwrap();
+        assert!(errors.starts_with("Could not parse `"));
+        assert!(errors.ends_with("\nKept the previous config\n"));
+    }
+
+    #[test]
+    fn test_repl_learns_unknown_words() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tranclator.toml");
+        std::fs::write(
+            &path,
+            "# Mine\n[[language]]\nname = \"test\"\nlower-mode = \"lower\"\n\n[language.dict]\nhello = \"hola\" # hi\n",
+        )
+        .unwrap();
+        let config = load_config(&[path.to_str().unwrap().to_string()]).unwrap();
+        let mut output = Vec::new();
+        let mut errors = Vec::new();
+
+        Repl {
+            config_paths: vec![path.to_str().unwrap().to_string()],
+            quiet: true,
+            learning: Some(Learning::default()),
+            ..Repl::for_test(config.languages)
+        }
+        .run(
+            &mut "hello cat dog Cat\ngato\n\ncat dog\nquit\n".as_bytes(),
+            &mut output,
+            &mut errors,
+        )
+        .unwrap();
 
-        assert_eq!(translate("hello world", &language), "hola mundo");
-        assert_eq!(translate("Hello WorLd", &language), "hola mundo");
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            ">>> hola cat dog cat\ncat = dog = >>> gato dog\n>>> "
+        );
+        assert_eq!(
+            std::fs::read_to_string(&path).unwrap(),
+            "# Mine\n[[language]]\nname = \"test\"\nlower-mode = \"lower\"\n\n[language.dict]\nhello = \"hola\" # hi\ncat = \"gato\"\n"
+        );
     }
 }
@@ -0,0 +1,937 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+const CONFIG: &str = r#"
+[global]
+default-language = "test"
+
+[[language]]
+name = "test"
+lower-mode = "lower"
+
+[language.dict]
+"hello" = "hola"
+"world" = "mundo"
+"#;
+
+fn write_config(dir: &tempfile::TempDir) -> PathBuf {
+    let path = dir.path().join("tranclator.toml");
+    std::fs::write(&path, CONFIG).unwrap();
+    path
+}
+
+fn run(args: &[&str], stdin: &str) -> Output {
+    run_in(None, &[], args, stdin)
+}
+
+/// Runs the binary in `dir` (or the current directory) with `envs` set on top
+/// of an environment without `TRANCLATOR_CONFIG` or `XDG_CONFIG_HOME`.
+fn run_in(dir: Option<&Path>, envs: &[(&str, &Path)], args: &[&str], stdin: &str) -> Output {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_tranclator"));
+    command
+        .env_remove("TRANCLATOR_CONFIG")
+        .env_remove("XDG_CONFIG_HOME")
+        .envs(envs.iter().copied());
+
+    if let Some(dir) = dir {
+        command.current_dir(dir);
+    }
+
+    let mut child = command
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn translates_piped_stdin() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = write_config(&dir);
+
+    let output = run(
+        &["--config-path", config.to_str().unwrap(), "--no-clipboard"],
+        "hello world\nhello there\n",
+    );
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "hola mundo\nhola there\n"
+    );
+}
+
+#[test]
+fn translates_input_file_into_output_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = write_config(&dir);
+    let input = dir.path().join("input.txt");
+    let output_path = dir.path().join("output.txt");
+    std::fs::write(&input, "hello\nworld\n\n").unwrap();
+
+    let output = run(
+        &[
+            "--config-path",
+            config.to_str().unwrap(),
+            "--no-clipboard",
+            "--input-file",
+            input.to_str().unwrap(),
+            "--output-file",
+            output_path.to_str().unwrap(),
+        ],
+        "",
+    );
+
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        std::fs::read_to_string(&output_path).unwrap(),
+        "hola\nmundo\n\n"
+    );
+}
+
+#[test]
+fn translates_input_file_to_stdout() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = write_config(&dir);
+    let input = dir.path().join("input.txt");
+    std::fs::write(&input, "hello world\n").unwrap();
+
+    let output = run(
+        &[
+            "--config-path",
+            config.to_str().unwrap(),
+            "--no-clipboard",
+            "--input-file",
+            input.to_str().unwrap(),
+        ],
+        "",
+    );
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "hola mundo\n");
+}
+
+#[test]
+fn only_matching_translates_tagged_lines() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = write_config(&dir);
+    let input = dir.path().join("input.txt");
+    let output_path = dir.path().join("output.txt");
+    std::fs::write(&input, "[t] hello world\nhello world\n\n[t] world\n").unwrap();
+
+    let output = run(
+        &[
+            "--config-path",
+            config.to_str().unwrap(),
+            "--no-clipboard",
+            "--only-matching",
+            r"^\[t\]",
+            "--input-file",
+            input.to_str().unwrap(),
+            "--output-file",
+            output_path.to_str().unwrap(),
+        ],
+        "",
+    );
+
+    assert!(output.status.success());
+    assert_eq!(
+        std::fs::read_to_string(&output_path).unwrap(),
+        "[t] hola mundo\nhello world\n\n[t] mundo\n"
+    );
+}
+
+#[test]
+fn verbose_logs_language_resolution() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = write_config(&dir);
+
+    let output = run(
+        &[
+            "--config-path",
+            config.to_str().unwrap(),
+            "--no-clipboard",
+            "--verbose",
+        ],
+        "hello\n",
+    );
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "hola\n");
+    assert!(stderr.contains("Using language `test` from `default-language` in the config"));
+    assert!(stderr.contains("Language `test` has 2 dictionary entries"));
+}
+
+#[test]
+fn no_trim_keeps_surrounding_whitespace() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = write_config(&dir);
+    let args = ["--config-path", config.to_str().unwrap(), "--no-clipboard"];
+    let input = "\n  hello world  \n\n";
+
+    let output = run(&args, input);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "  hola mundo\n");
+
+    let output = run(&[&args[..], &["--no-trim"]].concat(), input);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "\n  hola mundo  \n\n"
+    );
+}
+
+#[test]
+fn max_replacements_caps_substitutions() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = write_config(&dir);
+
+    let output = run(
+        &[
+            "--config-path",
+            config.to_str().unwrap(),
+            "--no-clipboard",
+            "--max-replacements",
+            "2",
+            "--verbose",
+        ],
+        "hello world hello world\n",
+    );
+
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "hola mundo hello world\n"
+    );
+    assert!(
+        String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("Stopped translating after the maximum of 2 replacements")
+    );
+}
+
+#[test]
+fn time_reports_durations_on_stderr() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = write_config(&dir);
+
+    let output = run(
+        &[
+            "--config-path",
+            config.to_str().unwrap(),
+            "--no-clipboard",
+            "--time",
+        ],
+        "hello world\n",
+    );
+
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "hola mundo\n");
+    assert!(stderr.contains("Loading the config took "), "{stderr}");
+    assert!(stderr.contains("Translating took "), "{stderr}");
+}
+
+#[test]
+fn in_place_overwrites_input_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = write_config(&dir);
+    let input = dir.path().join("input.txt");
+    std::fs::write(&input, "hello\nworld\n").unwrap();
+    let args = [
+        "--config-path",
+        config.to_str().unwrap(),
+        "--no-clipboard",
+        "--input-file",
+        input.to_str().unwrap(),
+        "--in-place",
+    ];
+
+    let output = run(&args, "");
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert_eq!(std::fs::read_to_string(&input).unwrap(), "hola\nmundo\n");
+    assert!(!dir.path().join("input.txt.bak").exists());
+
+    std::fs::write(&input, "hello world").unwrap();
+    let output = run(&[&args[..], &["--backup"]].concat(), "");
+    assert!(output.status.success());
+    assert_eq!(std::fs::read_to_string(&input).unwrap(), "hola mundo");
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("input.txt.bak")).unwrap(),
+        "hello world"
+    );
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 3);
+}
+
+#[test]
+fn output_encoding_transcodes_output_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("tranclator.toml");
+    std::fs::write(&path, CONFIG.replace("hola", "olá")).unwrap();
+    let output_file = dir.path().join("out.txt");
+    let args = [
+        "--config-path",
+        path.to_str().unwrap(),
+        "--no-clipboard",
+        "--text",
+        "hello world",
+        "--output-file",
+        output_file.to_str().unwrap(),
+        "--output-encoding",
+    ];
+
+    let output = run(&[&args[..], &["latin1"]].concat(), "");
+    assert!(output.status.success());
+    assert_eq!(std::fs::read(&output_file).unwrap(), b"ol\xe1 mundo");
+
+    std::fs::write(&path, CONFIG.replace("hola", "\u{3053}\u{3093}")).unwrap();
+    let output = run(&[&args[..], &["latin1"]].concat(), "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("can't be represented"));
+}
+
+#[test]
+fn capitalization_overrides_lower_mode() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = write_config(&dir);
+    let args = [
+        "--config-path",
+        config.to_str().unwrap(),
+        "--no-clipboard",
+        "--text",
+        "hello world",
+    ];
+
+    let output = run(&[&args[..], &["--capitalization", "title"]].concat(), "");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "Hola Mundo\n");
+    assert_eq!(std::fs::read_to_string(&config).unwrap(), CONFIG);
+
+    let output = run(&args, "");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hola mundo\n");
+
+    let output = run(&[&args[..], &["--capitalization", "shouty"]].concat(), "");
+    assert_eq!(output.status.code(), Some(2));
+}
+
+#[test]
+fn strict_fails_on_untranslated_words() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = write_config(&dir);
+    let args = [
+        "--config-path",
+        config.to_str().unwrap(),
+        "--no-clipboard",
+        "--strict",
+        "--text",
+    ];
+
+    let output = run(&[&args[..], &["Hello, world! 42"]].concat(), "");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hola, mundo! 42\n");
+
+    let output = run(&[&args[..], &["hello there, world of There"]].concat(), "");
+    assert_eq!(output.status.code(), Some(5));
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        "Untranslated words: there, of\n"
+    );
+}
+
+#[test]
+fn in_place_requires_input_file() {
+    let output = run(&["--in-place", "--no-clipboard"], "hello\n");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn count_only_prints_number_of_matches() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = write_config(&dir);
+
+    let output = run(
+        &["--config-path", config.to_str().unwrap(), "--count-only"],
+        "hello world\nhello there\n",
+    );
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "3\n");
+}
+
+#[test]
+fn explain_does_not_need_a_clipboard() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = write_config(&dir);
+
+    let output = run(
+        &[
+            "--config-path",
+            config.to_str().unwrap(),
+            "--explain",
+            "--text",
+            "hello",
+        ],
+        "",
+    );
+
+    assert!(output.status.success());
+    assert!(
+        String::from_utf8(output.stdout)
+            .unwrap()
+            .starts_with("\"hello\": translated")
+    );
+}
+
+#[test]
+fn reports_missing_input_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = write_config(&dir);
+    let input = dir.path().join("missing.txt");
+
+    let output = run(
+        &[
+            "--config-path",
+            config.to_str().unwrap(),
+            "--no-clipboard",
+            "--input-file",
+            input.to_str().unwrap(),
+        ],
+        "",
+    );
+
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        format!("Could not find `{}`\n", input.display())
+    );
+}
+
+#[test]
+fn check_fails_on_invalid_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("tranclator.toml");
+    std::fs::write(
+        &config,
+        "[global]\ndefault-language = \"missing\"\n[[language]]\nname = \"test\"\nlower-mode = \"lower\"\ndict = {}\n",
+    )
+    .unwrap();
+
+    let output = run(&["--config-path", config.to_str().unwrap(), "check"], "");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        concat!(
+            "`default-language` in `[global]` names `missing`, which is not a defined language\n",
+            "Language `test` has an empty dictionary\n"
+        )
+    );
+
+    let config = write_config(&dir);
+    let output = run(&["--config-path", config.to_str().unwrap(), "check"], "");
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "No problems found\n"
+    );
+}
+
+#[test]
+fn exits_with_2_when_config_is_missing() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("missing.toml");
+
+    let output = run(
+        &["--config-path", config.to_str().unwrap(), "--text", "hello"],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        format!("Could not find `{}`\n", config.display())
+    );
+}
+
+#[test]
+fn exits_with_3_when_config_does_not_parse() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("tranclator.toml");
+    std::fs::write(&config, "[[language]]\nname = 1\n").unwrap();
+
+    let output = run(
+        &["--config-path", config.to_str().unwrap(), "--text", "hello"],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(3));
+}
+
+#[test]
+fn reports_dangling_default_language() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("tranclator.toml");
+    std::fs::write(
+        &config,
+        CONFIG.replace("default-language = \"test\"", "default-language = \"tets\""),
+    )
+    .unwrap();
+
+    let output = run(
+        &[
+            "--config-path",
+            config.to_str().unwrap(),
+            "--no-clipboard",
+            "--text",
+            "hello",
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(4));
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "`default-language` in `[global]` names `tets`, which is not a defined language, did you mean `test`?\n"
+    );
+
+    let output = run(
+        &[
+            "--config-path",
+            config.to_str().unwrap(),
+            "--no-clipboard",
+            "--language",
+            "test",
+            "--text",
+            "hello",
+        ],
+        "",
+    );
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "hola\n");
+}
+
+#[test]
+fn exits_with_4_when_language_is_unknown() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = write_config(&dir);
+
+    let output = run(
+        &[
+            "--config-path",
+            config.to_str().unwrap(),
+            "--no-clipboard",
+            "--language",
+            "missing",
+            "--text",
+            "hello",
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(4));
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "Language missing not found\n"
+    );
+}
+
+#[test]
+fn warns_about_keys_that_collide_after_case_folding() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("tranclator.toml");
+    std::fs::write(
+        &config,
+        r#"
+[[language]]
+name = "test"
+lower-mode = "lower"
+
+[language.dict]
+"Cat" = "kat"
+"cat" = "poes"
+"#,
+    )
+    .unwrap();
+
+    let output = run(
+        &[
+            "--config-path",
+            config.to_str().unwrap(),
+            "--no-clipboard",
+            "-l",
+            "test",
+        ],
+        "cat\n",
+    );
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "kat\n");
+    assert!(String::from_utf8(output.stderr).unwrap().contains(
+        "Keys `Cat` and `cat` in language `test` match the same text, only `Cat` is used"
+    ));
+
+    let args = [
+        "--config-path",
+        config.to_str().unwrap(),
+        "--no-clipboard",
+        "-l",
+        "test",
+    ];
+    let output = run(&[&args[..], &["--quiet"]].concat(), "cat\n");
+    assert!(output.stderr.is_empty());
+
+    let output = run_in(
+        None,
+        &[("RUST_LOG", Path::new("tranclator=off"))],
+        &args,
+        "cat\n",
+    );
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn prints_json_report() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = write_config(&dir);
+
+    let output = run(
+        &[
+            "--config-path",
+            config.to_str().unwrap(),
+            "--no-clipboard",
+            "--format",
+            "json",
+        ],
+        "hello big world\n",
+    );
+
+    assert!(output.status.success());
+
+    let report: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(report["original"], "hello big world");
+    assert_eq!(report["translated"], "hola big mundo");
+    assert_eq!(report["language"], "test");
+    assert_eq!(
+        report["substitutions"],
+        serde_json::json!([
+            { "key": "hello", "source": { "start": 0, "end": 5 }, "target": { "start": 0, "end": 4 } },
+            { "key": "world", "source": { "start": 10, "end": 15 }, "target": { "start": 9, "end": 14 } },
+        ])
+    );
+}
+
+#[test]
+fn rules_add_and_override_dictionary_entries() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = write_config(&dir);
+
+    let output = run(
+        &[
+            "--config-path",
+            config.to_str().unwrap(),
+            "--no-clipboard",
+            "-l",
+            "test",
+            "--rule",
+            "foo=bar",
+            "--rule",
+            "hello=hi",
+            "--text",
+            "foo hello world",
+        ],
+        "",
+    );
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "bar hi mundo\n");
+}
+
+#[test]
+fn rejects_rules_without_equals_sign() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = write_config(&dir);
+
+    let output = run(
+        &[
+            "--config-path",
+            config.to_str().unwrap(),
+            "--no-clipboard",
+            "--rule",
+            "foo",
+            "--text",
+            "foo",
+        ],
+        "",
+    );
+
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8(output.stderr)
+            .unwrap()
+            .contains("there is no `=` in `foo`")
+    );
+}
+
+#[test]
+fn translates_with_rules_alone_without_a_config() {
+    let dir = tempfile::tempdir().unwrap();
+    let missing = dir.path().join("tranclator.toml");
+
+    let output = run(
+        &[
+            "--config-path",
+            missing.to_str().unwrap(),
+            "--no-clipboard",
+            "--lower-mode",
+            "upper",
+            "--rule",
+            "foo=bar",
+            "--text",
+            "foo baz",
+        ],
+        "",
+    );
+
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "BAR BAZ\n");
+}
+
+#[test]
+fn config_path_precedence() {
+    let dir = tempfile::tempdir().unwrap();
+    let write = |name: &str, translation: &str| {
+        let path = dir.path().join(name);
+        std::fs::write(
+            &path,
+            format!(
+                "[[language]]\nname = \"test\"\nlower-mode = \"lower\"\ndict = {{ hello = \"{translation}\" }}\n"
+            ),
+        )
+        .unwrap();
+        path
+    };
+    let default = write("tranclator.toml", "default");
+    let env = write("env.toml", "env");
+    let cli = write("cli.toml", "cli");
+    let translate = |envs: &[(&str, &Path)], args: &[&str]| {
+        let args = [args, &["--no-clipboard", "-l", "test", "--text", "hello"]].concat();
+        let output = run_in(Some(dir.path()), envs, &args, "");
+        String::from_utf8(output.stdout).unwrap()
+    };
+    let env_var = [("TRANCLATOR_CONFIG", env.as_path())];
+    let cli_arg = ["--config-path", cli.to_str().unwrap()];
+
+    assert_eq!(translate(&[], &[]), "default\n");
+    assert_eq!(translate(&env_var, &[]), "env\n");
+    assert_eq!(translate(&[], &cli_arg), "cli\n");
+    assert_eq!(translate(&env_var, &cli_arg), "cli\n");
+
+    std::fs::remove_file(default).unwrap();
+    assert_eq!(translate(&env_var, &[]), "env\n");
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn finds_config_in_user_config_dir() {
+    let home = tempfile::tempdir().unwrap();
+    let work = tempfile::tempdir().unwrap();
+    let envs = [("HOME", home.path())];
+    let args = ["--no-clipboard", "--text", "hello"];
+
+    let output = run_in(Some(work.path()), &envs, &args, "");
+    assert_eq!(output.status.code(), Some(2));
+    let user_config = home.path().join(".config/tranclator/config.toml");
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        format!(
+            "Could not find a config file, looked for `tranclator.toml` and `{}`. Use --config-path or TRANCLATOR_CONFIG to load another file\n",
+            user_config.display()
+        )
+    );
+
+    std::fs::create_dir_all(user_config.parent().unwrap()).unwrap();
+    std::fs::write(&user_config, CONFIG).unwrap();
+
+    let output = run_in(Some(work.path()), &envs, &args, "");
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "hola\n");
+}
+
+#[test]
+fn translates_several_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = write_config(&dir);
+    let a = dir.path().join("a.txt");
+    let b = dir.path().join("b.md");
+    std::fs::write(&a, "hello\n").unwrap();
+    std::fs::write(&b, "hello world").unwrap();
+
+    let output = run(
+        &[
+            "--config-path",
+            config.to_str().unwrap(),
+            "--no-clipboard",
+            a.to_str().unwrap(),
+            b.to_str().unwrap(),
+        ],
+        "",
+    );
+
+    assert!(output.status.success());
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("a.out")).unwrap(),
+        "hola\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(dir.path().join("b.out")).unwrap(),
+        "hola mundo"
+    );
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        format!(
+            "Translated `{}` to `{}`\nTranslated `{}` to `{}`\n",
+            a.display(),
+            dir.path().join("a.out").display(),
+            b.display(),
+            dir.path().join("b.out").display()
+        )
+    );
+}
+
+#[test]
+fn translates_directory_tree() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = write_config(&dir);
+    let input = dir.path().join("docs");
+    let output_dir = dir.path().join("translated");
+    std::fs::create_dir_all(input.join("nested")).unwrap();
+    std::fs::write(input.join("a.txt"), "hello\n").unwrap();
+    std::fs::write(input.join("nested/b.txt"), "world").unwrap();
+    std::fs::write(input.join("c.md"), "hello").unwrap();
+    std::fs::write(input.join("d.txt"), [0xff, 0xfe, 0x00]).unwrap();
+
+    let output = run(
+        &[
+            "--config-path",
+            config.to_str().unwrap(),
+            "--no-clipboard",
+            "--recursive",
+            input.to_str().unwrap(),
+            "--output-dir",
+            output_dir.to_str().unwrap(),
+            "--glob",
+            "*.txt",
+        ],
+        "",
+    );
+
+    assert!(output.status.success());
+    assert_eq!(
+        std::fs::read_to_string(output_dir.join("a.txt")).unwrap(),
+        "hola\n"
+    );
+    assert_eq!(
+        std::fs::read_to_string(output_dir.join("nested/b.txt")).unwrap(),
+        "mundo"
+    );
+    assert!(!output_dir.join("c.md").exists());
+    assert!(!output_dir.join("d.txt").exists());
+    assert!(
+        String::from_utf8(output.stderr)
+            .unwrap()
+            .contains(&format!("Skipped `{}`", input.join("d.txt").display()))
+    );
+}
+
+#[test]
+fn exits_with_0_on_success() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = write_config(&dir);
+
+    let output = run(
+        &[
+            "--config-path",
+            config.to_str().unwrap(),
+            "--no-clipboard",
+            "--text",
+            "hello",
+        ],
+        "",
+    );
+    assert_eq!(output.status.code(), Some(0));
+}
+
+/// Clipboard access fails without a display, so these runs only succeed if the
+/// clipboard is never touched.
+#[cfg(target_os = "linux")]
+fn run_without_display(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_tranclator"))
+        .args(args)
+        .env("DISPLAY", "invalid:0")
+        .env_remove("WAYLAND_DISPLAY")
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn global_copy_to_clipboard_false_skips_clipboard() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = dir.path().join("tranclator.toml");
+    std::fs::write(
+        &config,
+        CONFIG.replace("[global]", "[global]\ncopy-to-clipboard = false"),
+    )
+    .unwrap();
+
+    let output =
+        run_without_display(&["--config-path", config.to_str().unwrap(), "--text", "hello"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "hola\n");
+
+    let config = write_config(&dir);
+    let output =
+        run_without_display(&["--config-path", config.to_str().unwrap(), "--text", "hello"]);
+    assert!(!output.status.success());
+}
+
+#[cfg(target_os = "linux")]
+#[test]
+fn dry_run_lists_substitutions_without_clipboard() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = write_config(&dir);
+
+    let output = run_without_display(&[
+        "--config-path",
+        config.to_str().unwrap(),
+        "--dry-run",
+        "--text",
+        "Hello big world",
+    ]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "\"Hello\" -> \"hola\" at position 0\n\"world\" -> \"mundo\" at position 10\nhola big mundo\n"
+    );
+}